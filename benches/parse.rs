@@ -0,0 +1,67 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use torr::bencoding::{borrowed::BorrowedValue, parse::try_parse_value, value::Value};
+
+fn string(s: &str) -> Value {
+    Value::String(s.as_bytes().to_vec())
+}
+
+fn dict<const N: usize>(entries: [(&str, Value); N]) -> Value {
+    Value::Dictionary(entries.into_iter().collect())
+}
+
+/// A multi-file hybrid-like torrent with ~6 MB of hashes.
+fn large_torrent() -> Vec<u8> {
+    let files = (0..5_000)
+        .map(|i| {
+            dict([
+                ("length", Value::Integer(1 << 20)),
+                (
+                    "path",
+                    Value::List(vec![string("dir"), string(&format!("file-{}.bin", i))]),
+                ),
+            ])
+        })
+        .collect();
+    let pieces = (0..100_000u32)
+        .flat_map(|i| i.to_be_bytes().repeat(5))
+        .collect();
+    let layers = (0..4_000u32)
+        .map(|i| {
+            let root = i.to_be_bytes().repeat(8);
+            (root, Value::String(vec![i as u8; 32 * 32]))
+        })
+        .collect();
+
+    dict([
+        ("announce", string("http://tracker.example/announce")),
+        (
+            "info",
+            dict([
+                ("files", Value::List(files)),
+                ("name", string("dataset")),
+                ("piece length", Value::Integer(1 << 18)),
+                ("pieces", Value::String(pieces)),
+            ]),
+        ),
+        ("piece layers", Value::Dictionary(layers)),
+    ])
+    .to_bytes()
+}
+
+fn parse(c: &mut Criterion) {
+    let source = large_torrent();
+    let mut group = c.benchmark_group("parse large torrent");
+    group.bench_function("iterator", |b| {
+        b.iter(|| try_parse_value(black_box(&source).iter().copied()).unwrap())
+    });
+    group.bench_function("borrowed", |b| {
+        b.iter(|| BorrowedValue::parse(black_box(&source)).unwrap())
+    });
+    group.bench_function("borrowed to owned", |b| {
+        b.iter(|| BorrowedValue::parse(black_box(&source)).unwrap().to_value())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -1,4 +1,17 @@
+pub mod access;
+pub mod borrowed;
+pub mod dictionary;
+pub mod diff;
+pub mod encode;
+pub mod integer;
 pub mod json;
+pub mod macros;
 pub mod parse;
+pub mod path;
+pub mod print;
+pub mod query;
+#[cfg(feature = "serde")]
+pub mod serde;
+pub mod stream;
 pub mod utils;
 pub mod value;
@@ -0,0 +1,191 @@
+use std::fmt::Display;
+
+use super::dictionary::Dictionary;
+use super::integer::IntegerError;
+use super::path::{format_path, PathSegment};
+use super::value::{Value, ValueType};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccessErrorKind {
+    UnexpectedType {
+        expected: ValueType,
+        found: ValueType,
+    },
+    MissingKey,
+    IndexOutOfBounds {
+        len: usize,
+    },
+    /// The integer does not fit into `i64`.
+    IntegerOutOfRange,
+    InvalidUtf8,
+}
+
+/// Why a typed accessor failed, and where: `path` leads from the value the
+/// lookup started at to the offending (or missing) value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessError {
+    pub kind: AccessErrorKind,
+    pub path: Vec<PathSegment>,
+}
+
+impl AccessError {
+    fn new(kind: AccessErrorKind) -> Self {
+        AccessError {
+            kind,
+            path: Vec::new(),
+        }
+    }
+
+    fn unexpected_type(value: &Value, expected: ValueType) -> Self {
+        AccessError::new(AccessErrorKind::UnexpectedType {
+            expected,
+            found: value.get_type(),
+        })
+    }
+}
+
+impl Display for AccessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let path = if self.path.is_empty() {
+            "value".to_owned()
+        } else {
+            format_path(&self.path)
+        };
+        match &self.kind {
+            AccessErrorKind::UnexpectedType { expected, found } => {
+                write!(f, "{} is {:?}, expected {:?}", path, found, expected)
+            }
+            AccessErrorKind::MissingKey => write!(f, "{} is missing", path),
+            AccessErrorKind::IndexOutOfBounds { len } => {
+                write!(f, "{} is out of bounds for a list of {}", path, len)
+            }
+            AccessErrorKind::IntegerOutOfRange => write!(f, "{} does not fit into i64", path),
+            AccessErrorKind::InvalidUtf8 => write!(f, "{} is not valid UTF-8", path),
+        }
+    }
+}
+
+impl std::error::Error for AccessError {}
+
+pub type AccessResult<T> = std::result::Result<T, AccessError>;
+
+impl Value {
+    pub fn as_bytes(&self) -> AccessResult<&[u8]> {
+        match self {
+            Value::String(bytes) => Ok(bytes),
+            _ => Err(AccessError::unexpected_type(self, ValueType::String)),
+        }
+    }
+
+    pub fn as_str(&self) -> AccessResult<&str> {
+        std::str::from_utf8(self.as_bytes()?)
+            .map_err(|_| AccessError::new(AccessErrorKind::InvalidUtf8))
+    }
+
+    pub fn as_int(&self) -> AccessResult<i64> {
+        self.as_i64().map_err(|e| match e {
+            IntegerError::OutOfRange => AccessError::new(AccessErrorKind::IntegerOutOfRange),
+            _ => AccessError::unexpected_type(self, ValueType::Integer),
+        })
+    }
+
+    pub fn as_list(&self) -> AccessResult<&[Value]> {
+        match self {
+            Value::List(values) => Ok(values),
+            _ => Err(AccessError::unexpected_type(self, ValueType::List)),
+        }
+    }
+
+    pub fn as_dict(&self) -> AccessResult<&Dictionary> {
+        match self {
+            Value::Dictionary(kv) => Ok(kv),
+            _ => Err(AccessError::unexpected_type(self, ValueType::Dictionary)),
+        }
+    }
+
+    /// Follows `path` through nested dictionaries and lists.
+    ///
+    /// ```ignore
+    /// let length = torrent
+    ///     .get_path(&["info".into(), "files".into(), 0.into(), "length".into()])?
+    ///     .as_int()?;
+    /// ```
+    pub fn get_path(&self, path: &[PathSegment]) -> AccessResult<&Value> {
+        let mut current = self;
+        for (depth, segment) in path.iter().enumerate() {
+            let next =
+                match segment {
+                    PathSegment::Key(key) => current.as_dict().and_then(|kv| {
+                        kv.get(key)
+                            .ok_or(AccessError::new(AccessErrorKind::MissingKey))
+                    }),
+                    PathSegment::Index(ind) => current.as_list().and_then(|values| {
+                        values.get(*ind).ok_or(AccessError::new(
+                            AccessErrorKind::IndexOutOfBounds { len: values.len() },
+                        ))
+                    }),
+                };
+            current = next.map_err(|mut e| {
+                let end = match e.kind {
+                    AccessErrorKind::UnexpectedType { .. } => depth,
+                    _ => depth + 1,
+                };
+                e.path = path[..end].to_vec();
+                e
+            })?;
+        }
+        Ok(current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bencoding::utils::str_to_value;
+
+    use super::*;
+
+    #[test]
+    fn test_accessors() {
+        let value = str_to_value("d4:infod5:filesld6:lengthi7e4:pathl1:aeeee4:name2:ée").unwrap();
+        let length = value
+            .get_path(&["info".into(), "files".into(), 0.into(), "length".into()])
+            .unwrap();
+        assert_eq!(length.as_int(), Ok(7));
+        assert_eq!(value["name"].as_str(), Ok("\u{e9}"));
+        assert_eq!(value["info"]["files"].as_list().unwrap().len(), 1);
+        assert_eq!(value.as_dict().unwrap().len(), 2);
+
+        let big = str_to_value("i9223372036854775808e").unwrap();
+        assert_eq!(
+            big.as_int().unwrap_err().kind,
+            AccessErrorKind::IntegerOutOfRange
+        );
+        assert_eq!(
+            Value::String(vec![0xff]).as_str().unwrap_err().kind,
+            AccessErrorKind::InvalidUtf8
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let value = str_to_value("d4:infod5:filesld6:lengthi7eeeee").unwrap();
+        let error = |path: &[PathSegment]| value.get_path(path).unwrap_err().to_string();
+
+        assert_eq!(
+            error(&["info".into(), "files".into(), "length".into()]),
+            "['info']['files'] is List, expected Dictionary"
+        );
+        assert_eq!(
+            error(&["info".into(), "files".into(), 1.into()]),
+            "['info']['files'][1] is out of bounds for a list of 1"
+        );
+        assert_eq!(
+            error(&["info".into(), "name".into()]),
+            "['info']['name'] is missing"
+        );
+        assert_eq!(
+            value.as_list().unwrap_err().to_string(),
+            "value is Dictionary, expected List"
+        );
+    }
+}
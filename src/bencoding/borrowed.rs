@@ -0,0 +1,388 @@
+use super::integer::BigInteger;
+use super::parse::{
+    integer_from_digits, IParseResult, ParseError, ParseErrorKind, ParseOptions, Usage,
+};
+use super::path::PathSegment;
+use super::value::{Value, ValueType};
+
+/// A bencoded value borrowing from the source buffer.
+///
+/// Unlike [`Value`] it does not copy strings, which makes it cheap to parse
+/// large `pieces` or `piece layers` blobs, and it remembers the exact bytes
+/// every value was parsed from (see [`BorrowedValue::raw`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BorrowedValue<'a> {
+    raw: &'a [u8],
+    kind: BorrowedKind<'a>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BorrowedKind<'a> {
+    String(&'a [u8]),
+    Integer(i64),
+    BigInteger(BigInteger),
+    List(Vec<BorrowedValue<'a>>),
+    Dictionary(Vec<(&'a [u8], BorrowedValue<'a>)>),
+}
+
+impl<'a> BorrowedValue<'a> {
+    pub fn parse(source: &'a [u8]) -> IParseResult<Self> {
+        Self::parse_with_options(source, ParseOptions::default())
+    }
+
+    pub fn parse_with_options(source: &'a [u8], options: ParseOptions) -> IParseResult<Self> {
+        let mut parser = Parser {
+            source,
+            pos: 0,
+            options,
+            usage: Usage::default(),
+        };
+        let value = parser.parse_value().map_err(ParseError::finish)?;
+        if parser.options.strict && parser.pos < source.len() {
+            return Err(parser.error(ParseErrorKind::TrailingBytes));
+        }
+        Ok(value)
+    }
+
+    /// The bytes this value was parsed from, e.g. what an infohash is
+    /// computed over.
+    pub fn raw(&self) -> &'a [u8] {
+        self.raw
+    }
+
+    pub fn kind(&self) -> &BorrowedKind<'a> {
+        &self.kind
+    }
+
+    pub fn get_type(&self) -> ValueType {
+        match self.kind {
+            BorrowedKind::String(_) => ValueType::String,
+            BorrowedKind::Integer(_) | BorrowedKind::BigInteger(_) => ValueType::Integer,
+            BorrowedKind::List(_) => ValueType::List,
+            BorrowedKind::Dictionary(_) => ValueType::Dictionary,
+        }
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&BorrowedValue<'a>> {
+        match &self.kind {
+            BorrowedKind::Dictionary(kv) => {
+                kv.iter()
+                    .find_map(|(k, v)| if *k == key { Some(v) } else { None })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match &self.kind {
+            BorrowedKind::String(bytes) => Value::String(bytes.to_vec()),
+            BorrowedKind::Integer(i) => Value::Integer(*i),
+            BorrowedKind::BigInteger(i) => i.clone().into(),
+            BorrowedKind::List(values) => Value::List(values.iter().map(Self::to_value).collect()),
+            BorrowedKind::Dictionary(kv) => {
+                Value::Dictionary(kv.iter().map(|(k, v)| (*k, v.to_value())).collect())
+            }
+        }
+    }
+}
+
+impl From<&BorrowedValue<'_>> for Value {
+    fn from(value: &BorrowedValue<'_>) -> Self {
+        value.to_value()
+    }
+}
+
+struct Parser<'a> {
+    source: &'a [u8],
+    pos: usize,
+    options: ParseOptions,
+    usage: Usage,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.source.get(self.pos).copied()
+    }
+
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError::new(kind, self.pos, self.peek())
+    }
+
+    fn parse_value(&mut self) -> IParseResult<BorrowedValue<'a>> {
+        let start = self.pos;
+        match self.peek() {
+            Some(b) => self.usage.element(&self.options, start, b)?,
+            None => return Err(self.error(ParseErrorKind::UnexpectedEndOfInput)),
+        }
+        let kind = match self.peek() {
+            Some(b'i') => self.parse_integer()?,
+            Some(b @ (b'l' | b'd')) => {
+                self.usage.enter(&self.options, start, b)?;
+                let kind = if b == b'l' {
+                    BorrowedKind::List(self.parse_list()?)
+                } else {
+                    BorrowedKind::Dictionary(self.parse_dictionary()?)
+                };
+                self.usage.leave();
+                kind
+            }
+            Some(b'0'..=b'9') => BorrowedKind::String(self.parse_string()?),
+            _ => return Err(self.error(ParseErrorKind::InvalidPrefix)),
+        };
+        Ok(BorrowedValue {
+            raw: &self.source[start..self.pos],
+            kind,
+        })
+    }
+
+    fn parse_unsigned_integer(&mut self) -> IParseResult<u64> {
+        let start = self.pos;
+        let digits = self.source[start..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        if digits == 0 {
+            return Err(self.error(ParseErrorKind::UnsignedIntegerExpected));
+        }
+        if self.options.strict && digits > 1 && self.source[start] == b'0' {
+            return Err(ParseError::new(
+                ParseErrorKind::LeadingZero,
+                start,
+                Some(b'0'),
+            ));
+        }
+        let mut num: u64 = 0;
+        for b in &self.source[start..start + digits] {
+            num = num
+                .checked_mul(10)
+                .and_then(|num| num.checked_add((b - b'0') as u64))
+                .ok_or_else(|| ParseError::new(ParseErrorKind::IntegerOverflow, start, None))?;
+        }
+        self.pos += digits;
+        Ok(num)
+    }
+
+    fn parse_string(&mut self) -> IParseResult<&'a [u8]> {
+        let start = self.pos;
+        let first = self.peek().unwrap_or_default();
+        let len = self.parse_unsigned_integer()?;
+        self.usage.string(&self.options, len, start, first)?;
+        if self.peek() != Some(b':') {
+            return Err(self.error(ParseErrorKind::ColonExpected));
+        }
+        self.pos += 1;
+        let available = (self.source.len() - self.pos) as u64;
+        if len > available {
+            self.pos = self.source.len();
+            return Err(self.error(ParseErrorKind::UnexpectedEndOfString));
+        }
+        let bytes = &self.source[self.pos..self.pos + len as usize];
+        self.pos += len as usize;
+        Ok(bytes)
+    }
+
+    fn parse_integer(&mut self) -> IParseResult<BorrowedKind<'a>> {
+        self.pos += 1;
+        let sign_offset = self.pos;
+        let negative = self.peek() == Some(b'-');
+        if negative {
+            self.pos += 1;
+        }
+        let start = self.pos;
+        let digits = self.source[start..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        if digits == 0 {
+            return Err(self.error(ParseErrorKind::UnsignedIntegerExpected));
+        }
+        if self.options.strict && digits > 1 && self.source[start] == b'0' {
+            return Err(ParseError::new(
+                ParseErrorKind::LeadingZero,
+                start,
+                Some(b'0'),
+            ));
+        }
+        if digits > self.options.max_string_length {
+            return Err(ParseError::new(
+                ParseErrorKind::MaxStringLengthExceeded,
+                start,
+                Some(self.source[start]),
+            ));
+        }
+        let digits = &self.source[start..start + digits];
+        if negative && digits.iter().all(|b| *b == b'0') {
+            return Err(ParseError::new(
+                ParseErrorKind::NegativeZeroOccurred,
+                sign_offset,
+                Some(b'-'),
+            ));
+        }
+        self.pos += digits.len();
+        let kind = match integer_from_digits(negative, digits) {
+            Value::BigInteger(i) => BorrowedKind::BigInteger(i),
+            value => BorrowedKind::Integer(value.as_i64().unwrap()),
+        };
+        match self.peek() {
+            Some(b'e') => {
+                self.pos += 1;
+                Ok(kind)
+            }
+            _ => Err(self.error(ParseErrorKind::IntegerSuffixExpected)),
+        }
+    }
+
+    fn parse_list(&mut self) -> IParseResult<Vec<BorrowedValue<'a>>> {
+        self.pos += 1;
+        let mut list = Vec::new();
+        loop {
+            match self.peek() {
+                Some(b'e') => {
+                    self.pos += 1;
+                    return Ok(list);
+                }
+                Some(_) => {
+                    let value = self
+                        .parse_value()
+                        .map_err(|e| e.within(PathSegment::Index(list.len())))?;
+                    list.push(value);
+                }
+                None => return Err(self.error(ParseErrorKind::UnexpectedEndOfInput)),
+            }
+        }
+    }
+
+    fn parse_dictionary(&mut self) -> IParseResult<Vec<(&'a [u8], BorrowedValue<'a>)>> {
+        self.pos += 1;
+        let mut dict: Vec<(&'a [u8], BorrowedValue<'a>)> = Vec::new();
+        loop {
+            match self.peek() {
+                Some(b'e') => {
+                    self.pos += 1;
+                    return Ok(dict);
+                }
+                Some(key_byte @ b'0'..=b'9') => {
+                    let key_offset = self.pos;
+                    self.usage.element(&self.options, key_offset, key_byte)?;
+                    let key = self.parse_string()?;
+                    let within = |e: ParseError| e.within(PathSegment::Key(key.to_vec()));
+                    if self.options.strict {
+                        if let Some((previous, _)) = dict.last() {
+                            let kind = match key.cmp(previous) {
+                                std::cmp::Ordering::Less => Some(ParseErrorKind::UnsortedKeys),
+                                std::cmp::Ordering::Equal => Some(ParseErrorKind::DuplicateKey),
+                                std::cmp::Ordering::Greater => None,
+                            };
+                            if let Some(kind) = kind {
+                                return Err(within(ParseError::new(
+                                    kind,
+                                    key_offset,
+                                    Some(key_byte),
+                                )));
+                            }
+                        }
+                    }
+                    let value = self.parse_value().map_err(within)?;
+                    dict.push((key, value));
+                }
+                Some(_) => return Err(self.error(ParseErrorKind::KeyExpectedToBeAString)),
+                None => return Err(self.error(ParseErrorKind::ExpectedDictionaryKey)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bencoding::parse::try_parse_value_with_options;
+
+    #[test]
+    fn test_matches_owned_parser() {
+        for source in [
+            "4:spam",
+            "0:",
+            "i-42e",
+            "i03e",
+            "le",
+            "l4:spami1ee",
+            "d3:cow3:moo4:spaml1:a1:bee",
+            "d4:infod5:filesld6:lengthi1e4:pathl1:aeeee",
+            "i-0e",
+            "i42",
+            "5:spam",
+            "l4:spam",
+            "di1ei2ee",
+            "d4:spami1e3:cowi2ee",
+            "i1ei2e",
+            "4294967295:aaaa",
+            "i18446744073709551616e",
+            "i-00018446744073709551616e",
+            "i-9223372036854775808e",
+        ] {
+            for options in [ParseOptions::lenient(), ParseOptions::strict()] {
+                let owned = try_parse_value_with_options(source.bytes(), options.clone());
+                let borrowed = BorrowedValue::parse_with_options(source.as_bytes(), options);
+                assert_eq!(borrowed.map(|v| v.to_value()), owned, "{}", source);
+            }
+        }
+    }
+
+    #[test]
+    fn test_zero_copy() {
+        let source = b"d4:infod6:pieces4:\x00\x01\x02\x03ee";
+        let value = BorrowedValue::parse(source).unwrap();
+        let info = value.get(b"info").unwrap();
+        assert_eq!(info.raw(), &source[7..23]);
+
+        let pieces = match info.get(b"pieces").unwrap().kind() {
+            BorrowedKind::String(bytes) => *bytes,
+            _ => unreachable!(),
+        };
+        assert_eq!(pieces, &[0, 1, 2, 3]);
+        assert!(std::ptr::eq(pieces.as_ptr(), source[18..].as_ptr()));
+        assert_eq!(info.get_type(), ValueType::Dictionary);
+        assert_eq!(value.get(b"missing"), None);
+    }
+
+    #[test]
+    fn test_limits_match_owned_parser() {
+        let nested = vec![b'l'; 1_000_000];
+        let strings = b"l"
+            .iter()
+            .chain(b"1:a".repeat(100).iter())
+            .chain(b"e")
+            .copied()
+            .collect::<Vec<_>>();
+        for (source, options) in [
+            (&nested[..], ParseOptions::default()),
+            (
+                &strings[..],
+                ParseOptions {
+                    max_elements: 50,
+                    ..ParseOptions::default()
+                },
+            ),
+            (
+                &strings[..],
+                ParseOptions {
+                    max_allocation: 1000,
+                    ..ParseOptions::default()
+                },
+            ),
+            (
+                b"18446744073709551615:a",
+                ParseOptions {
+                    max_string_length: usize::MAX,
+                    max_allocation: usize::MAX,
+                    ..ParseOptions::default()
+                },
+            ),
+        ] {
+            let owned = try_parse_value_with_options(source.iter().copied(), options.clone());
+            let borrowed = BorrowedValue::parse_with_options(source, options);
+            assert!(owned.is_err());
+            assert_eq!(borrowed.map(|v| v.to_value()), owned);
+        }
+    }
+}
@@ -0,0 +1,340 @@
+use std::fmt::Debug;
+
+use super::value::Value;
+
+/// A bencode dictionary: values keyed by byte strings.
+///
+/// Entries are kept sorted by key, the order bencode requires, so lookups
+/// are a binary search. A dictionary created with
+/// [`Dictionary::with_insertion_order`] iterates (and is encoded) in the
+/// order its keys were first inserted instead, which lets non-canonical
+/// input be written back byte for byte; lookups then go through a separate
+/// sorted index and stay logarithmic.
+#[derive(Clone, Default)]
+pub struct Dictionary {
+    entries: Vec<(Vec<u8>, Value)>,
+    /// Positions in `entries` ordered by key, kept only when `entries` is in
+    /// insertion order.
+    sorted: Option<Vec<usize>>,
+}
+
+impl Dictionary {
+    pub fn new() -> Self {
+        Dictionary::default()
+    }
+
+    pub fn with_insertion_order() -> Self {
+        Dictionary {
+            entries: Vec::new(),
+            sorted: Some(Vec::new()),
+        }
+    }
+
+    /// Builds a dictionary from entries in insertion order, as
+    /// [`Dictionary::insert`] would one by one but sorting only once, so
+    /// that large unsorted input takes O(n log n) rather than O(n²).
+    pub fn from_entries(mut entries: Vec<(Vec<u8>, Value)>, preserve_order: bool) -> Self {
+        let is_sorted = entries.windows(2).all(|w| w[0].0 < w[1].0);
+        if is_sorted {
+            let sorted = preserve_order.then(|| (0..entries.len()).collect());
+            return Dictionary { entries, sorted };
+        }
+
+        // Positions by key; the sort is stable, so among equal keys the
+        // first inserted comes first and the last inserted last.
+        let mut order = (0..entries.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| entries[a].0.cmp(&entries[b].0));
+        let mut kept = Vec::with_capacity(order.len());
+        let mut removed = vec![false; entries.len()];
+        let groups = order
+            .chunk_by(|&a, &b| entries[a].0 == entries[b].0)
+            .collect::<Vec<_>>();
+        for group in groups {
+            let (first, last) = (group[0], group[group.len() - 1]);
+            if first != last {
+                let value = std::mem::replace(&mut entries[last].1, Value::Integer(0));
+                entries[first].1 = value;
+                for &p in &group[1..] {
+                    removed[p] = true;
+                }
+            }
+            kept.push(first);
+        }
+
+        if !preserve_order {
+            let mut entries = entries.into_iter().map(Some).collect::<Vec<_>>();
+            let entries = kept
+                .into_iter()
+                .map(|p| entries[p].take().expect("each position is kept once"))
+                .collect();
+            return Dictionary {
+                entries,
+                sorted: None,
+            };
+        }
+        let mut new_position = vec![0; entries.len()];
+        let mut count = 0;
+        for (p, is_removed) in removed.iter().enumerate() {
+            new_position[p] = count;
+            count += !is_removed as usize;
+        }
+        let entries = entries
+            .into_iter()
+            .zip(&removed)
+            .filter(|(_, is_removed)| !**is_removed)
+            .map(|(entry, _)| entry)
+            .collect();
+        let sorted = kept.into_iter().map(|p| new_position[p]).collect();
+        Dictionary {
+            entries,
+            sorted: Some(sorted),
+        }
+    }
+
+    pub fn preserves_insertion_order(&self) -> bool {
+        self.sorted.is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: impl AsRef<[u8]>) -> Option<&Value> {
+        let position = self.position(key.as_ref())?;
+        Some(&self.entries[position].1)
+    }
+
+    pub fn get_mut(&mut self, key: impl AsRef<[u8]>) -> Option<&mut Value> {
+        let position = self.position(key.as_ref())?;
+        Some(&mut self.entries[position].1)
+    }
+
+    pub fn contains_key(&self, key: impl AsRef<[u8]>) -> bool {
+        self.position(key.as_ref()).is_some()
+    }
+
+    /// The `index`-th entry in iteration order.
+    pub fn get_index(&self, index: usize) -> Option<(&[u8], &Value)> {
+        self.entries.get(index).map(|(k, v)| (k.as_slice(), v))
+    }
+
+    /// Inserts or replaces the value of `key`, returning the previous one.
+    /// A replaced key keeps its place in the insertion order.
+    pub fn insert(&mut self, key: impl Into<Vec<u8>>, value: Value) -> Option<Value> {
+        let key = key.into();
+        match self.search(&key) {
+            Ok(rank) => {
+                let position = self.to_position(rank);
+                Some(std::mem::replace(&mut self.entries[position].1, value))
+            }
+            Err(rank) => {
+                match &mut self.sorted {
+                    None => self.entries.insert(rank, (key, value)),
+                    Some(sorted) => {
+                        sorted.insert(rank, self.entries.len());
+                        self.entries.push((key, value));
+                    }
+                }
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: impl AsRef<[u8]>) -> Option<Value> {
+        let rank = self.search(key.as_ref()).ok()?;
+        let position = match &mut self.sorted {
+            None => rank,
+            Some(sorted) => {
+                let position = sorted.remove(rank);
+                for p in sorted.iter_mut().filter(|p| **p > position) {
+                    *p -= 1;
+                }
+                position
+            }
+        };
+        Some(self.entries.remove(position).1)
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter(self.entries.iter())
+    }
+
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &[u8]> + ExactSizeIterator {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &Value> + ExactSizeIterator {
+        self.iter().map(|(_, v)| v)
+    }
+
+    /// Rank of `key` among the sorted keys.
+    fn search(&self, key: &[u8]) -> Result<usize, usize> {
+        match &self.sorted {
+            None => self
+                .entries
+                .binary_search_by(|(k, _)| k.as_slice().cmp(key)),
+            Some(sorted) => sorted.binary_search_by(|&p| self.entries[p].0.as_slice().cmp(key)),
+        }
+    }
+
+    fn to_position(&self, rank: usize) -> usize {
+        match &self.sorted {
+            None => rank,
+            Some(sorted) => sorted[rank],
+        }
+    }
+
+    fn position(&self, key: &[u8]) -> Option<usize> {
+        self.search(key).ok().map(|rank| self.to_position(rank))
+    }
+}
+
+/// Dictionaries are equal when they iterate over the same entries in the
+/// same order, i.e. when they encode to the same bytes.
+impl PartialEq for Dictionary {
+    fn eq(&self, other: &Self) -> bool {
+        self.entries == other.entries
+    }
+}
+
+impl Eq for Dictionary {}
+
+impl Debug for Dictionary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(self.iter().map(|(k, v)| (String::from_utf8_lossy(k), v)))
+            .finish()
+    }
+}
+
+pub struct Iter<'a>(std::slice::Iter<'a, (Vec<u8>, Value)>);
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a [u8], &'a Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(k, v)| (k.as_slice(), v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for Iter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|(k, v)| (k.as_slice(), v))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+impl<'a> IntoIterator for &'a Dictionary {
+    type Item = (&'a [u8], &'a Value);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for Dictionary {
+    type Item = (Vec<u8>, Value);
+    type IntoIter = std::vec::IntoIter<(Vec<u8>, Value)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<K: Into<Vec<u8>>> Extend<(K, Value)> for Dictionary {
+    fn extend<T: IntoIterator<Item = (K, Value)>>(&mut self, iter: T) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+/// Collects into a sorted dictionary; later duplicates replace earlier ones.
+impl<K: Into<Vec<u8>>> FromIterator<(K, Value)> for Dictionary {
+    fn from_iter<T: IntoIterator<Item = (K, Value)>>(iter: T) -> Self {
+        let entries = iter.into_iter().map(|(k, v)| (k.into(), v)).collect();
+        Dictionary::from_entries(entries, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(d: &Dictionary) -> Vec<&str> {
+        d.keys().map(|k| std::str::from_utf8(k).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_sorted() {
+        let mut d = Dictionary::new();
+        assert_eq!(d.insert("spam", Value::Integer(1)), None);
+        assert_eq!(d.insert("cow", Value::Integer(2)), None);
+        assert_eq!(d.insert("eggs", Value::Integer(3)), None);
+        assert_eq!(d.insert("cow", Value::Integer(4)), Some(Value::Integer(2)));
+        assert_eq!(keys(&d), ["cow", "eggs", "spam"]);
+        assert_eq!(d.get("cow"), Some(&Value::Integer(4)));
+        assert_eq!(d.get(b"missing"), None);
+        assert_eq!(d.remove("eggs"), Some(Value::Integer(3)));
+        assert_eq!(keys(&d), ["cow", "spam"]);
+        assert!(!d.contains_key("eggs"));
+    }
+
+    #[test]
+    fn test_insertion_order() {
+        let mut d = Dictionary::with_insertion_order();
+        for (k, v) in [("spam", 1), ("cow", 2), ("eggs", 3), ("ant", 4)] {
+            d.insert(k, Value::Integer(v));
+        }
+        d.insert("cow", Value::Integer(5));
+        assert_eq!(keys(&d), ["spam", "cow", "eggs", "ant"]);
+        assert_eq!(d.get("cow"), Some(&Value::Integer(5)));
+        assert_eq!(d.remove("spam"), Some(Value::Integer(1)));
+        assert_eq!(keys(&d), ["cow", "eggs", "ant"]);
+        for (k, v) in [("ant", 4), ("cow", 5), ("eggs", 3)] {
+            assert_eq!(d.get(k), Some(&Value::Integer(v)));
+        }
+        assert_eq!(d.get("spam"), None);
+
+        let sorted: Dictionary = d.clone().into_iter().collect();
+        assert_eq!(keys(&sorted), ["ant", "cow", "eggs"]);
+        assert_ne!(sorted, d);
+    }
+
+    #[test]
+    fn test_from_entries() {
+        let entries = [("spam", 1), ("cow", 2), ("spam", 3), ("ant", 4), ("cow", 5)]
+            .into_iter()
+            .map(|(k, v)| (k.as_bytes().to_vec(), Value::Integer(v)))
+            .collect::<Vec<_>>();
+        for preserve_order in [false, true] {
+            let mut expected = if preserve_order {
+                Dictionary::with_insertion_order()
+            } else {
+                Dictionary::new()
+            };
+            for (k, v) in entries.clone() {
+                expected.insert(k, v);
+            }
+            let d = Dictionary::from_entries(entries.clone(), preserve_order);
+            assert_eq!(d, expected);
+            assert_eq!(d.preserves_insertion_order(), preserve_order);
+            for (k, v) in [("ant", 4), ("cow", 5), ("spam", 3)] {
+                assert_eq!(d.get(k), Some(&Value::Integer(v)));
+            }
+        }
+        let sorted = Dictionary::from_entries(entries[3..].to_vec(), true);
+        assert_eq!(keys(&sorted), ["ant", "cow"]);
+        assert_eq!(sorted.get("cow"), Some(&Value::Integer(5)));
+    }
+}
@@ -0,0 +1,159 @@
+//! Structural comparison of two bencoded values.
+
+use std::collections::BTreeSet;
+use std::fmt::Display;
+
+use super::path::{format_path, PathSegment};
+use super::print::{describe_value, PrintOptions};
+use super::value::Value;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change<'a> {
+    Added(&'a Value),
+    Removed(&'a Value),
+    /// The value was replaced, either by a different scalar or by a value
+    /// of another type.
+    Changed {
+        old: &'a Value,
+        new: &'a Value,
+    },
+}
+
+/// One change, located by its path from the compared roots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference<'a> {
+    pub path: Vec<PathSegment>,
+    pub change: Change<'a>,
+}
+
+impl Difference<'_> {
+    /// Whether the change lies at or below `prefix`.
+    pub fn is_under(&self, prefix: &[PathSegment]) -> bool {
+        self.path.starts_with(prefix)
+    }
+}
+
+/// Formats as `+ path = new`, `- path = old` or `~ path: old -> new`.
+impl Display for Difference<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let describe = |value| describe_value(value, &PrintOptions::default());
+        let path = if self.path.is_empty() {
+            "value".to_owned()
+        } else {
+            format_path(&self.path)
+        };
+        match self.change {
+            Change::Added(new) => write!(f, "+ {} = {}", path, describe(new)),
+            Change::Removed(old) => write!(f, "- {} = {}", path, describe(old)),
+            Change::Changed { old, new } => {
+                write!(f, "~ {}: {} -> {}", path, describe(old), describe(new))
+            }
+        }
+    }
+}
+
+/// Lists what changed from `old` to `new`.
+///
+/// Dictionaries are compared key by key and lists index by index, so an
+/// element inserted in the middle of a list shows up as changes to every
+/// later index. Added and removed subtrees are reported once, at their
+/// root. Differences come in path order, with keys sorted.
+pub fn diff<'a>(old: &'a Value, new: &'a Value) -> Vec<Difference<'a>> {
+    let mut res = Vec::new();
+    diff_into(old, new, &mut Vec::new(), &mut res);
+    res
+}
+
+fn diff_into<'a>(
+    old: &'a Value,
+    new: &'a Value,
+    path: &mut Vec<PathSegment>,
+    res: &mut Vec<Difference<'a>>,
+) {
+    match (old, new) {
+        (Value::Dictionary(old_kv), Value::Dictionary(new_kv)) => {
+            let keys = old_kv.keys().chain(new_kv.keys()).collect::<BTreeSet<_>>();
+            for key in keys {
+                path.push(PathSegment::Key(key.to_vec()));
+                diff_child(old_kv.get(key), new_kv.get(key), path, res);
+                path.pop();
+            }
+        }
+        (Value::List(old_values), Value::List(new_values)) => {
+            for ind in 0..old_values.len().max(new_values.len()) {
+                path.push(PathSegment::Index(ind));
+                diff_child(old_values.get(ind), new_values.get(ind), path, res);
+                path.pop();
+            }
+        }
+        _ if old != new => res.push(Difference {
+            path: path.clone(),
+            change: Change::Changed { old, new },
+        }),
+        _ => {}
+    }
+}
+
+/// Compares a child present on at least one side.
+fn diff_child<'a>(
+    old: Option<&'a Value>,
+    new: Option<&'a Value>,
+    path: &mut Vec<PathSegment>,
+    res: &mut Vec<Difference<'a>>,
+) {
+    let change = match (old, new) {
+        (Some(old), Some(new)) => return diff_into(old, new, path, res),
+        (Some(old), None) => Change::Removed(old),
+        (None, Some(new)) => Change::Added(new),
+        (None, None) => unreachable!(),
+    };
+    res.push(Difference {
+        path: path.clone(),
+        change,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bencoding::utils::str_to_value;
+
+    fn lines(old: &str, new: &str) -> Vec<String> {
+        let old = str_to_value(old).unwrap();
+        let new = str_to_value(new).unwrap();
+        diff(&old, &new).iter().map(|d| d.to_string()).collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        assert!(lines("d1:ai1e1:bl1:xee", "d1:ai1e1:bl1:xee").is_empty());
+        assert_eq!(
+            lines(
+                "d7:comment3:old4:infod6:lengthi1e4:name1:aee",
+                "d4:infod6:lengthi2e4:name1:a6:sourceli1eeee"
+            ),
+            [
+                "- ['comment'] = 'old'",
+                "~ ['info']['length']: 1 -> 2",
+                "+ ['info']['source'] = list of 1",
+            ]
+        );
+        assert_eq!(
+            lines("l1:ai1ei2ee", "l1:bi1ee"),
+            ["~ [0]: 'a' -> 'b'", "- [2] = 2"]
+        );
+        assert_eq!(lines("i1e", "de"), ["~ value: 1 -> dictionary of 0"]);
+    }
+
+    #[test]
+    fn test_is_under() {
+        let old = str_to_value("d8:announce1:a4:infod4:name1:aee").unwrap();
+        let new = str_to_value("d8:announce1:b4:infod4:name1:bee").unwrap();
+        let info: &[PathSegment] = &["info".into()];
+        let under_info = diff(&old, &new)
+            .iter()
+            .map(|d| d.is_under(info))
+            .collect::<Vec<_>>();
+        assert_eq!(under_info, [false, true]);
+    }
+}
@@ -0,0 +1,98 @@
+use std::io::Write;
+
+use super::value::Value;
+
+impl Value {
+    /// Writes the bencoded form of the value.
+    ///
+    /// Dictionaries are written in their iteration order, which is sorted
+    /// by key unless they preserve insertion order, and integers are written
+    /// in their minimal form, so encoding a value with sorted dictionaries
+    /// always yields the canonical representation described in BEP 3.
+    pub fn encode(&self, w: &mut impl Write) -> std::io::Result<()> {
+        match self {
+            Value::String(bytes) => encode_bytes(bytes, w),
+            Value::Integer(i) => write!(w, "i{}e", i),
+            Value::BigInteger(i) => write!(w, "i{}e", i),
+            Value::List(values) => {
+                w.write_all(b"l")?;
+                for v in values {
+                    v.encode(w)?;
+                }
+                w.write_all(b"e")
+            }
+            Value::Dictionary(kv) => {
+                w.write_all(b"d")?;
+                for (k, v) in kv {
+                    encode_bytes(k, w)?;
+                    v.encode(w)?;
+                }
+                w.write_all(b"e")
+            }
+        }
+    }
+
+    /// Returns the bencoded form of the value.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.encode(&mut bytes)
+            .expect("writing to a Vec does not fail");
+        bytes
+    }
+}
+
+fn encode_bytes(bytes: &[u8], w: &mut impl Write) -> std::io::Result<()> {
+    write!(w, "{}:", bytes.len())?;
+    w.write_all(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bencoding::dictionary::Dictionary;
+    use crate::bencoding::utils::str_to_value;
+    #[allow(deprecated)]
+    use crate::bencoding::value::{IntoValue, Value};
+
+    fn encode_str(s: &str) -> String {
+        String::from_utf8(str_to_value(s).unwrap().to_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_string() {
+        assert_eq!(encode_str("4:spam"), "4:spam");
+        assert_eq!(encode_str("0:"), "0:");
+    }
+    #[test]
+    fn test_integer() {
+        assert_eq!(encode_str("i0e"), "i0e");
+        assert_eq!(encode_str("i-42e"), "i-42e");
+        assert_eq!(
+            Value::Integer(i64::MIN).to_bytes(),
+            b"i-9223372036854775808e"
+        );
+    }
+    #[test]
+    fn test_list() {
+        assert_eq!(encode_str("l4:spam4:eggse"), "l4:spam4:eggse");
+        assert_eq!(encode_str("le"), "le");
+    }
+    #[test]
+    #[allow(deprecated)]
+    fn test_dict_keys_are_sorted() {
+        assert_eq!(
+            encode_str("d4:spam4:eggs3:cow3:mooe"),
+            "d3:cow3:moo4:spam4:eggse"
+        );
+        assert_eq!(
+            [("b", 1i64), ("a", 2), ("ab", 3)].into_value().to_bytes(),
+            b"d1:ai2e2:abi3e1:bi1ee"
+        );
+    }
+    #[test]
+    fn test_insertion_order() {
+        let mut kv = Dictionary::with_insertion_order();
+        kv.insert("spam", Value::Integer(1));
+        kv.insert("cow", Value::Integer(2));
+        assert_eq!(Value::Dictionary(kv).to_bytes(), b"d4:spami1e3:cowi2ee");
+    }
+}
@@ -0,0 +1,204 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use super::value::{Value, ValueType};
+
+/// An integer of any size, kept as its canonical decimal digits.
+///
+/// Parsers produce [`Value::Integer`] for everything that fits into `i64`
+/// and `Value::BigInteger` only for the rest, so that every integer survives
+/// a round-trip exactly. Converting a `BigInteger` into a [`Value`] follows
+/// the same rule.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BigInteger(String);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegerError {
+    /// The value is not an integer at all.
+    NotAnInteger(ValueType),
+    /// The integer does not fit into the requested type.
+    OutOfRange,
+    /// The text is not a canonical decimal integer.
+    InvalidDigits,
+}
+
+impl Display for IntegerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IntegerError::NotAnInteger(found) => {
+                write!(f, "expected an integer, found {:?}", found)
+            }
+            IntegerError::OutOfRange => write!(f, "integer is out of range"),
+            IntegerError::InvalidDigits => write!(f, "invalid decimal integer"),
+        }
+    }
+}
+
+impl std::error::Error for IntegerError {}
+
+impl BigInteger {
+    /// Builds an integer from its sign and decimal digits, which may have
+    /// leading zeros. The digits must be ASCII digits.
+    pub(crate) fn from_digits(negative: bool, digits: &[u8]) -> Self {
+        debug_assert!(digits.iter().all(u8::is_ascii_digit));
+        let first = digits.iter().position(|b| *b != b'0');
+        let magnitude = match first {
+            Some(first) => &digits[first..],
+            None => return BigInteger("0".to_owned()),
+        };
+        let mut res = String::with_capacity(magnitude.len() + 1);
+        if negative {
+            res.push('-');
+        }
+        res.extend(magnitude.iter().map(|b| *b as char));
+        BigInteger(res)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.0.starts_with('-')
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        self.0.parse().ok()
+    }
+
+    pub fn to_u64(&self) -> Option<u64> {
+        self.0.parse().ok()
+    }
+
+    pub fn to_i128(&self) -> Option<i128> {
+        self.0.parse().ok()
+    }
+
+    pub fn to_u128(&self) -> Option<u128> {
+        self.0.parse().ok()
+    }
+}
+
+/// Accepts `0` and `-?[1-9][0-9]*`, the forms bencode allows between `i`
+/// and `e`.
+impl FromStr for BigInteger {
+    type Err = IntegerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, s),
+        };
+        let canonical = match digits.as_bytes() {
+            [] => false,
+            [b'0'] => !negative,
+            [first, rest @ ..] => {
+                (b'1'..=b'9').contains(first) && rest.iter().all(u8::is_ascii_digit)
+            }
+        };
+        if !canonical {
+            return Err(IntegerError::InvalidDigits);
+        }
+        Ok(BigInteger(s.to_owned()))
+    }
+}
+
+impl Display for BigInteger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<i64> for BigInteger {
+    fn from(i: i64) -> Self {
+        BigInteger(i.to_string())
+    }
+}
+
+impl From<u64> for BigInteger {
+    fn from(i: u64) -> Self {
+        BigInteger(i.to_string())
+    }
+}
+
+impl From<i128> for BigInteger {
+    fn from(i: i128) -> Self {
+        BigInteger(i.to_string())
+    }
+}
+
+impl From<u128> for BigInteger {
+    fn from(i: u128) -> Self {
+        BigInteger(i.to_string())
+    }
+}
+
+/// Narrows to [`Value::Integer`] when the integer fits into `i64`.
+impl From<BigInteger> for Value {
+    fn from(i: BigInteger) -> Self {
+        match i.to_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::BigInteger(i),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_digits() {
+        assert_eq!(BigInteger::from_digits(false, b"000").as_str(), "0");
+        assert_eq!(BigInteger::from_digits(true, b"0"), BigInteger::from(0i64));
+        assert_eq!(
+            BigInteger::from_digits(true, b"0018446744073709551616").as_str(),
+            "-18446744073709551616"
+        );
+    }
+
+    #[test]
+    fn test_from_str() {
+        for valid in ["0", "7", "-7", "123456789012345678901234567890"] {
+            assert_eq!(valid.parse::<BigInteger>().unwrap().as_str(), valid);
+        }
+        for invalid in ["", "-", "-0", "01", "1.5", "+1", "1e3", " 1"] {
+            assert_eq!(
+                invalid.parse::<BigInteger>(),
+                Err(IntegerError::InvalidDigits)
+            );
+        }
+    }
+
+    #[test]
+    fn test_conversions() {
+        let big: BigInteger = "18446744073709551615".parse().unwrap();
+        assert_eq!(big.to_i64(), None);
+        assert_eq!(big.to_u64(), Some(u64::MAX));
+        assert_eq!(Value::from(big.clone()), Value::BigInteger(big));
+        assert!(matches!(
+            Value::from(BigInteger::from(-5i64)),
+            Value::Integer(-5)
+        ));
+        assert_eq!(Value::from(BigInteger::from(5u128)), Value::Integer(5));
+        assert_eq!(
+            Value::from(BigInteger::from(i64::MIN as i128)),
+            Value::Integer(i64::MIN)
+        );
+    }
+
+    #[test]
+    fn test_value_equality() {
+        let five = Value::BigInteger(BigInteger::from(5i64));
+        assert_eq!(five, Value::Integer(5));
+        assert_eq!(Value::Integer(5), five);
+        assert_ne!(five, Value::Integer(6));
+        assert_eq!(
+            Value::List(vec![five]),
+            Value::List(vec![Value::Integer(5)])
+        );
+        let big: BigInteger = "18446744073709551615".parse().unwrap();
+        assert_ne!(Value::BigInteger(big), Value::Integer(-1));
+        assert_ne!(Value::Integer(5), Value::String(b"5".to_vec()));
+    }
+}
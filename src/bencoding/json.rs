@@ -1,51 +1,525 @@
+use std::fmt::Display;
+use std::io::{Read, Write};
+
+use super::dictionary::Dictionary;
+use super::integer::BigInteger;
+use super::parse::ParseOptions;
+use super::utils::{from_hex, to_base64, to_hex};
 use super::value::Value;
 
-pub fn write_as_json(value: &Value, w: &mut impl std::io::Write) -> std::io::Result<()> {
-    match value {
-        Value::String(v) => {
-            w.write("\"".as_bytes())?;
-            // TODO: add escaping
-            w.write(v)?;
-            w.write("\"".as_bytes())?;
-            Ok(())
+/// How strings that are not valid UTF-8 (e.g. `pieces`) are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BinaryEncoding {
+    /// A JSON string with the bytes in lowercase hex.
+    #[default]
+    Hex,
+    /// A JSON string with the bytes in standard base64.
+    Base64,
+    /// A `{"$bytes": "<hex>"}` object, which, unlike the other encodings,
+    /// can be told apart from text when reading the JSON back.
+    ///
+    /// Dictionary keys cannot be objects, so binary keys are written as
+    /// `"$bytes:<hex>"` and keys that start with `$` get another `$` in front.
+    Tagged,
+}
+
+pub const BYTES_TAG: &str = "$bytes";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JsonOptions {
+    pub binary: BinaryEncoding,
+    /// Pretty-prints with the given number of spaces per level when set.
+    pub indent: Option<usize>,
+}
+
+pub fn write_as_json(value: &Value, w: &mut impl Write) -> std::io::Result<()> {
+    write_as_json_with_options(value, w, &JsonOptions::default())
+}
+
+pub fn write_as_json_with_options(
+    value: &Value,
+    w: &mut impl Write,
+    options: &JsonOptions,
+) -> std::io::Result<()> {
+    write_value(value, w, options, 0)
+}
+
+fn write_escaped(s: &str, w: &mut impl Write) -> std::io::Result<()> {
+    w.write_all(b"\"")?;
+    let mut start = 0;
+    for (ind, c) in s.char_indices() {
+        let escape = match c {
+            '"' => "\\\"".to_owned(),
+            '\\' => "\\\\".to_owned(),
+            '\n' => "\\n".to_owned(),
+            '\r' => "\\r".to_owned(),
+            '\t' => "\\t".to_owned(),
+            '\u{08}' => "\\b".to_owned(),
+            '\u{0c}' => "\\f".to_owned(),
+            c if c < ' ' => format!("\\u{:04x}", c as u32),
+            _ => continue,
+        };
+        w.write_all(&s.as_bytes()[start..ind])?;
+        w.write_all(escape.as_bytes())?;
+        start = ind + c.len_utf8();
+    }
+    w.write_all(&s.as_bytes()[start..])?;
+    w.write_all(b"\"")
+}
+
+fn write_colon(w: &mut impl Write, options: &JsonOptions) -> std::io::Result<()> {
+    w.write_all(if options.indent.is_some() {
+        b": "
+    } else {
+        b":"
+    })
+}
+
+fn write_key(bytes: &[u8], w: &mut impl Write, options: &JsonOptions) -> std::io::Result<()> {
+    match (std::str::from_utf8(bytes), options.binary) {
+        (Ok(s), BinaryEncoding::Tagged) if s.starts_with('$') => {
+            write_escaped(&format!("${}", s), w)
+        }
+        (Ok(s), _) => write_escaped(s, w),
+        (Err(_), BinaryEncoding::Hex) => write_escaped(&to_hex(bytes), w),
+        (Err(_), BinaryEncoding::Base64) => write_escaped(&to_base64(bytes), w),
+        (Err(_), BinaryEncoding::Tagged) => {
+            write_escaped(&format!("{}:{}", BYTES_TAG, to_hex(bytes)), w)
         }
+    }
+}
+
+fn write_newline(w: &mut impl Write, options: &JsonOptions, level: usize) -> std::io::Result<()> {
+    match options.indent {
+        Some(indent) => write!(w, "\n{:width$}", "", width = indent * level),
+        None => Ok(()),
+    }
+}
+
+fn write_value(
+    value: &Value,
+    w: &mut impl Write,
+    options: &JsonOptions,
+    level: usize,
+) -> std::io::Result<()> {
+    match value {
+        Value::String(v) => match (std::str::from_utf8(v), options.binary) {
+            (Ok(s), _) => write_escaped(s, w),
+            (Err(_), BinaryEncoding::Hex) => write_escaped(&to_hex(v), w),
+            (Err(_), BinaryEncoding::Base64) => write_escaped(&to_base64(v), w),
+            (Err(_), BinaryEncoding::Tagged) => {
+                w.write_all(b"{")?;
+                write_newline(w, options, level + 1)?;
+                write_escaped(BYTES_TAG, w)?;
+                write_colon(w, options)?;
+                write_escaped(&to_hex(v), w)?;
+                write_newline(w, options, level)?;
+                w.write_all(b"}")
+            }
+        },
         Value::Integer(i) => {
             write!(w, "{}", i)
         }
+        Value::BigInteger(i) => {
+            write!(w, "{}", i)
+        }
         Value::List(ls) => {
-            w.write("[".as_bytes())?;
+            w.write_all(b"[")?;
             let mut first = true;
             for x in ls {
                 if first {
                     first = false;
                 } else {
-                    w.write(",".as_bytes()).map(|_| ())?
+                    w.write_all(b",")?
                 }
-                write_as_json(x, w)?;
+                write_newline(w, options, level + 1)?;
+                write_value(x, w, options, level + 1)?;
+            }
+            if !ls.is_empty() {
+                write_newline(w, options, level)?;
             }
-            w.write("]".as_bytes()).map(|_| ())
+            w.write_all(b"]")
         }
         Value::Dictionary(kv) => {
-            w.write("{".as_bytes()).map(|_| ())?;
+            w.write_all(b"{")?;
             let mut first = true;
             for (k, v) in kv {
                 if first {
                     first = false;
                 } else {
-                    w.write(",".as_bytes()).map(|_| ())?
+                    w.write_all(b",")?
                 }
-                write_as_json(k, w)?;
-                w.write(":".as_bytes()).map(|_| ())?;
-                write_as_json(v, w)?;
+                write_newline(w, options, level + 1)?;
+                write_key(k, w, options)?;
+                write_colon(w, options)?;
+                write_value(v, w, options, level + 1)?;
             }
-            w.write("}".as_bytes()).map(|_| ())
+            if !kv.is_empty() {
+                write_newline(w, options, level)?;
+            }
+            w.write_all(b"}")
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonErrorKind {
+    UnexpectedEndOfInput,
+    UnexpectedCharacter,
+    InvalidEscape,
+    ControlCharacter,
+    /// Bencode has no floating point numbers.
+    NotAnInteger,
+    /// `true`, `false` and `null` have no bencode counterpart.
+    UnsupportedLiteral,
+    InvalidBytes,
+    /// A key starting with a single `$` other than `$bytes:<hex>`.
+    UnknownTag,
+    DuplicateKey,
+    MaxDepthExceeded,
+    TrailingCharacters,
+}
+
+impl Display for JsonErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let description = match self {
+            JsonErrorKind::UnexpectedEndOfInput => "unexpected end of input",
+            JsonErrorKind::UnexpectedCharacter => "unexpected character",
+            JsonErrorKind::InvalidEscape => "invalid escape sequence",
+            JsonErrorKind::ControlCharacter => "unescaped control character in string",
+            JsonErrorKind::NotAnInteger => "number is not an integer",
+            JsonErrorKind::UnsupportedLiteral => "true, false and null are not supported",
+            JsonErrorKind::InvalidBytes => "$bytes must be a string of hex digits",
+            JsonErrorKind::UnknownTag => "keys starting with '$' must be escaped as '$$'",
+            JsonErrorKind::DuplicateKey => "dictionary key is duplicated",
+            JsonErrorKind::MaxDepthExceeded => "value is nested too deeply",
+            JsonErrorKind::TrailingCharacters => "unexpected characters after the value",
+        };
+        f.write_str(description)
+    }
+}
+
+#[derive(Debug)]
+pub enum JsonError {
+    Io(std::io::Error),
+    Syntax { kind: JsonErrorKind, offset: usize },
+}
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonError::Io(e) => write!(f, "{}", e),
+            JsonError::Syntax { kind, offset } => write!(f, "{} at offset {}", kind, offset),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+impl From<std::io::Error> for JsonError {
+    fn from(e: std::io::Error) -> Self {
+        JsonError::Io(e)
+    }
+}
+
+pub type JsonResult<T> = std::result::Result<T, JsonError>;
+
+/// Reads a value written by [`write_as_json_with_options`].
+///
+/// Binary strings are only recovered from the [`BinaryEncoding::Tagged`]
+/// form; hex and base64 strings written by the other policies come back as
+/// the text they were written as. Dictionary keys are sorted whatever their
+/// order in the JSON object, so the value encodes to canonical bencode.
+pub fn read_from_json(r: &mut impl Read) -> JsonResult<Value> {
+    let mut source = String::new();
+    r.read_to_string(&mut source)?;
+    let mut reader = JsonReader {
+        source: &source,
+        pos: 0,
+        depth: 0,
+        max_depth: ParseOptions::default().max_depth,
+    };
+    let value = reader.value()?;
+    reader.skip_whitespace();
+    if reader.pos < source.len() {
+        return Err(reader.error(JsonErrorKind::TrailingCharacters));
+    }
+    Ok(value)
+}
+
+struct JsonReader<'a> {
+    source: &'a str,
+    pos: usize,
+    depth: usize,
+    max_depth: usize,
+}
+
+impl JsonReader<'_> {
+    fn error(&self, kind: JsonErrorKind) -> JsonError {
+        self.error_at(kind, self.pos)
+    }
+
+    fn error_at(&self, kind: JsonErrorKind, offset: usize) -> JsonError {
+        JsonError::Syntax { kind, offset }
+    }
+
+    fn unexpected(&self) -> JsonError {
+        match self.peek() {
+            Some(_) => self.error(JsonErrorKind::UnexpectedCharacter),
+            None => self.error(JsonErrorKind::UnexpectedEndOfInput),
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.source.as_bytes().get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let b = self.peek()?;
+        self.pos += 1;
+        Some(b)
+    }
+
+    fn expect(&mut self, expected: u8) -> JsonResult<()> {
+        self.skip_whitespace();
+        if self.peek() != Some(expected) {
+            return Err(self.unexpected());
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn enter(&mut self) -> JsonResult<()> {
+        if self.depth >= self.max_depth {
+            return Err(self.error(JsonErrorKind::MaxDepthExceeded));
+        }
+        self.depth += 1;
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn value(&mut self) -> JsonResult<Value> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'"') => Ok(Value::String(self.string()?.into_bytes())),
+            Some(b'-' | b'0'..=b'9') => self.integer(),
+            Some(b'[') => self.list(),
+            Some(b'{') => self.object(),
+            Some(b't' | b'f' | b'n') => Err(self.error(JsonErrorKind::UnsupportedLiteral)),
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn integer(&mut self) -> JsonResult<Value> {
+        let start = self.pos;
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some(b'0') => self.pos += 1,
+            Some(b'1'..=b'9') => {
+                while let Some(b'0'..=b'9') = self.peek() {
+                    self.pos += 1;
+                }
+            }
+            _ => return Err(self.unexpected()),
+        }
+        match self.peek() {
+            Some(b'.' | b'e' | b'E') => Err(self.error_at(JsonErrorKind::NotAnInteger, start)),
+            Some(b'0'..=b'9') => Err(self.error(JsonErrorKind::UnexpectedCharacter)),
+            _ => {
+                let digits = &self.source[start..self.pos];
+                Ok(match digits.parse() {
+                    Ok(i) => Value::Integer(i),
+                    Err(_) => digits
+                        .parse::<BigInteger>()
+                        .expect("JSON integers are canonical")
+                        .into(),
+                })
+            }
+        }
+    }
+
+    fn string(&mut self) -> JsonResult<String> {
+        self.expect(b'"')?;
+        let mut res = String::new();
+        loop {
+            let start = self.pos;
+            while let Some(b) = self.peek() {
+                if b == b'"' || b == b'\\' || b < b' ' {
+                    break;
+                }
+                self.pos += 1;
+            }
+            res.push_str(&self.source[start..self.pos]);
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(res);
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    res.push(self.escape()?);
+                }
+                Some(_) => return Err(self.error(JsonErrorKind::ControlCharacter)),
+                None => return Err(self.error(JsonErrorKind::UnexpectedEndOfInput)),
+            }
+        }
+    }
+
+    fn escape(&mut self) -> JsonResult<char> {
+        let start = self.pos - 1;
+        let c = match self.next() {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{08}',
+            Some(b'f') => '\u{0c}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+                let high = self.hex4(start)?;
+                let code = if (0xd800..0xdc00).contains(&high) {
+                    if !self.source[self.pos..].starts_with("\\u") {
+                        return Err(self.error_at(JsonErrorKind::InvalidEscape, start));
+                    }
+                    self.pos += 2;
+                    let low = self.hex4(start)?;
+                    if !(0xdc00..0xe000).contains(&low) {
+                        return Err(self.error_at(JsonErrorKind::InvalidEscape, start));
+                    }
+                    0x10000 + ((high - 0xd800) << 10 | (low - 0xdc00))
+                } else {
+                    high
+                };
+                char::from_u32(code)
+                    .ok_or_else(|| self.error_at(JsonErrorKind::InvalidEscape, start))?
+            }
+            _ => return Err(self.error_at(JsonErrorKind::InvalidEscape, start)),
+        };
+        Ok(c)
+    }
+
+    fn hex4(&mut self, start: usize) -> JsonResult<u32> {
+        let digits = self
+            .source
+            .get(self.pos..self.pos + 4)
+            .filter(|digits| digits.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or_else(|| self.error_at(JsonErrorKind::InvalidEscape, start))?;
+        self.pos += 4;
+        Ok(u32::from_str_radix(digits, 16).unwrap())
+    }
+
+    fn list(&mut self) -> JsonResult<Value> {
+        self.enter()?;
+        let mut res = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+        } else {
+            loop {
+                res.push(self.value()?);
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(b',') => self.pos += 1,
+                    Some(b']') => {
+                        self.pos += 1;
+                        break;
+                    }
+                    _ => return Err(self.unexpected()),
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(Value::List(res))
+    }
+
+    fn object(&mut self) -> JsonResult<Value> {
+        self.enter()?;
+        let mut res = Dictionary::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+        } else {
+            loop {
+                self.skip_whitespace();
+                let key_start = self.pos;
+                let key = self.string()?;
+                self.expect(b':')?;
+                if key == BYTES_TAG && res.is_empty() {
+                    let value = self.bytes()?;
+                    self.expect(b'}')?;
+                    self.depth -= 1;
+                    return Ok(value);
+                }
+                let key = self.key(key, key_start)?;
+                if res.contains_key(&key) {
+                    return Err(self.error_at(JsonErrorKind::DuplicateKey, key_start));
+                }
+                let value = self.value()?;
+                res.insert(key, value);
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(b',') => self.pos += 1,
+                    Some(b'}') => {
+                        self.pos += 1;
+                        break;
+                    }
+                    _ => return Err(self.unexpected()),
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(Value::Dictionary(res))
+    }
+
+    fn bytes(&mut self) -> JsonResult<Value> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let hex = self.string()?;
+        from_hex(&hex)
+            .map(Value::String)
+            .ok_or_else(|| self.error_at(JsonErrorKind::InvalidBytes, start))
+    }
+
+    fn key(&self, key: String, start: usize) -> JsonResult<Vec<u8>> {
+        if key.starts_with("$$") {
+            Ok(key.into_bytes().split_off(1))
+        } else if let Some(hex) = key
+            .strip_prefix(BYTES_TAG)
+            .and_then(|rest| rest.strip_prefix(':'))
+        {
+            from_hex(hex).ok_or_else(|| self.error_at(JsonErrorKind::InvalidBytes, start))
+        } else if key.starts_with('$') {
+            Err(self.error_at(JsonErrorKind::UnknownTag, start))
+        } else {
+            Ok(key.into_bytes())
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
+    use super::super::parse::try_parse_value;
     use super::super::utils::str_to_json;
+    use super::*;
+
+    fn to_json(source: &[u8], binary: BinaryEncoding, indent: Option<usize>) -> String {
+        let value = try_parse_value(&mut source.iter().copied()).unwrap();
+        let mut bytes = Vec::new();
+        write_as_json_with_options(&value, &mut bytes, &JsonOptions { binary, indent }).unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
     #[test]
     fn test_string() {
         assert_eq!(str_to_json("4:spam"), "\"spam\"");
@@ -67,4 +541,157 @@ mod tests {
             "{\"spam\":\"egg\",\"spam2\":\"egg2\"}"
         );
     }
+    #[test]
+    fn test_escaping() {
+        assert_eq!(
+            str_to_json("9:a\"b\\c\n\t\x01\x1f"),
+            r#""a\"b\\c\n\t\u0001\u001f""#
+        );
+        assert_eq!(str_to_json("3:\u{e9}!"), "\"\u{e9}!\"");
+        assert_eq!(str_to_json("d3:\"k\"0:e"), r#"{"\"k\"":""}"#);
+    }
+    #[test]
+    fn test_binary() {
+        let source = b"d3:bin3:\xff\x00a4:\xffkey0:e";
+        assert_eq!(
+            to_json(source, BinaryEncoding::Hex, None),
+            r#"{"bin":"ff0061","ff6b6579":""}"#
+        );
+        assert_eq!(
+            to_json(source, BinaryEncoding::Base64, None),
+            r#"{"bin":"/wBh","/2tleQ==":""}"#
+        );
+        assert_eq!(
+            to_json(source, BinaryEncoding::Tagged, None),
+            r#"{"bin":{"$bytes":"ff0061"},"$bytes:ff6b6579":""}"#
+        );
+        assert_eq!(
+            to_json(b"d6:$bytes1:ae", BinaryEncoding::Tagged, None),
+            r#"{"$$bytes":"a"}"#
+        );
+    }
+    #[test]
+    fn test_pretty() {
+        assert_eq!(
+            to_json(
+                b"d5:emptyde4:infod6:lengthi1ee4:listli1eled3:bin1:\xffeee",
+                BinaryEncoding::Tagged,
+                Some(2)
+            ),
+            "{\n  \"empty\": {},\n  \"info\": {\n    \"length\": 1\n  },\n  \"list\": [\n    1,\n    [],\n    {\n      \"bin\": {\n        \"$bytes\": \"ff\"\n      }\n    }\n  ]\n}"
+        );
+    }
+
+    fn from_json(json: &str) -> JsonResult<Value> {
+        read_from_json(&mut json.as_bytes())
+    }
+
+    fn syntax_error(json: &str) -> (JsonErrorKind, usize) {
+        match from_json(json) {
+            Err(JsonError::Syntax { kind, offset }) => (kind, offset),
+            res => panic!("expected a syntax error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_read_json() {
+        let value = from_json(
+            r#" {"a": [1, -2, "x\"é😀"], "$$b": {"$bytes": "FF00"},
+                "$bytes:ff": {}, "c": []} "#,
+        )
+        .unwrap();
+        assert_eq!(
+            value.to_bytes(),
+            b"d2:$b2:\xff\x001:ali1ei-2e8:x\"\xc3\xa9\xf0\x9f\x98\x80e1:cle1:\xffdee"
+        );
+        assert_eq!(
+            from_json("[-0, 99999999999999999999]").unwrap().to_bytes(),
+            b"li0ei99999999999999999999ee"
+        );
+    }
+
+    #[test]
+    fn test_read_json_errors() {
+        assert_eq!(syntax_error("1.5"), (JsonErrorKind::NotAnInteger, 0));
+        assert_eq!(syntax_error("[1e3]"), (JsonErrorKind::NotAnInteger, 1));
+        assert_eq!(syntax_error("01"), (JsonErrorKind::UnexpectedCharacter, 1));
+        assert_eq!(
+            syntax_error("[null]"),
+            (JsonErrorKind::UnsupportedLiteral, 1)
+        );
+        assert_eq!(
+            syntax_error(r#"{"a":1,"a":2}"#),
+            (JsonErrorKind::DuplicateKey, 7)
+        );
+        assert_eq!(syntax_error(r#"{"$a":1}"#), (JsonErrorKind::UnknownTag, 1));
+        assert_eq!(
+            syntax_error(r#"{"$bytes":"f"}"#),
+            (JsonErrorKind::InvalidBytes, 10)
+        );
+        assert_eq!(
+            syntax_error(r#"{"$bytes":"ff","a":1}"#),
+            (JsonErrorKind::UnexpectedCharacter, 14)
+        );
+        assert_eq!(
+            syntax_error(r#""\ud83d""#),
+            (JsonErrorKind::InvalidEscape, 1)
+        );
+        assert_eq!(
+            syntax_error("\"a\nb\""),
+            (JsonErrorKind::ControlCharacter, 2)
+        );
+        assert_eq!(
+            syntax_error("[1,"),
+            (JsonErrorKind::UnexpectedEndOfInput, 3)
+        );
+        assert_eq!(
+            syntax_error("[1 2]"),
+            (JsonErrorKind::UnexpectedCharacter, 3)
+        );
+        assert_eq!(syntax_error("1 2"), (JsonErrorKind::TrailingCharacters, 2));
+        assert_eq!(
+            syntax_error(&"[".repeat(200)),
+            (JsonErrorKind::MaxDepthExceeded, 128)
+        );
+    }
+
+    fn arb_string() -> impl Strategy<Value = Vec<u8>> {
+        prop_oneof![
+            any::<Vec<u8>>(),
+            "\\$*\\PC*".prop_map(String::into_bytes),
+            "[\\x00-\\x1f\"\\\\]*".prop_map(String::into_bytes),
+        ]
+    }
+
+    fn arb_value() -> impl Strategy<Value = Value> {
+        let leaf = prop_oneof![
+            arb_string().prop_map(Value::String),
+            any::<i64>().prop_map(Value::Integer),
+            any::<i128>().prop_map(|i| BigInteger::from(i).into()),
+            "-?[1-9][0-9]{20,40}".prop_map(|i| Value::BigInteger(i.parse().unwrap())),
+        ];
+        leaf.prop_recursive(4, 64, 8, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..8).prop_map(Value::List),
+                prop::collection::btree_map(arb_string(), inner, 0..8)
+                    .prop_map(|entries| { Value::Dictionary(entries.into_iter().collect()) }),
+            ]
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn test_json_round_trip(value in arb_value(), indent in prop::option::of(0..4usize)) {
+            let bencode = value.to_bytes();
+            let parsed = try_parse_value(&mut bencode.iter().copied()).unwrap();
+            let mut json = Vec::new();
+            let options = JsonOptions {
+                binary: BinaryEncoding::Tagged,
+                indent,
+            };
+            write_as_json_with_options(&parsed, &mut json, &options).unwrap();
+            let read = read_from_json(&mut json.as_slice()).unwrap();
+            prop_assert_eq!(read.to_bytes(), bencode);
+        }
+    }
 }
@@ -0,0 +1,142 @@
+use super::value::Value;
+
+/// A dictionary value for [`bencode!`](crate::bencode): anything that
+/// converts into a [`Value`], or an `Option` of it, where `None` leaves the
+/// entry out. Bencode has no null, so optional fields such as `comment` are
+/// simply missing.
+pub trait IntoEntry {
+    fn into_entry(self) -> Option<Value>;
+}
+
+impl<T: Into<Value>> IntoEntry for T {
+    fn into_entry(self) -> Option<Value> {
+        Some(self.into())
+    }
+}
+
+impl<T: Into<Value>> IntoEntry for Option<T> {
+    fn into_entry(self) -> Option<Value> {
+        self.map(Into::into)
+    }
+}
+
+/// Builds a [`Value`](crate::bencoding::value::Value).
+///
+/// `{ key => value, ... }` makes a dictionary, `[value, ...]` a list, and
+/// anything else is converted with `Value::from`. Keys are anything that
+/// converts into `Vec<u8>`. The outermost braces may be dropped.
+///
+/// ```ignore
+/// let response = bencode! {
+///     "interval" => 1800,
+///     "peers" => [{ "ip" => ip, "port" => 6881 }],
+///     "warning message" => warning, // an Option, left out when None
+/// };
+/// ```
+#[macro_export]
+macro_rules! bencode {
+    (@dict $kv:ident) => {};
+    (@dict $kv:ident $key:expr => { $($value:tt)* } $(, $($rest:tt)*)?) => {
+        $kv.insert($key, $crate::bencode!({ $($value)* }));
+        $crate::bencode!(@dict $kv $($($rest)*)?);
+    };
+    (@dict $kv:ident $key:expr => [ $($value:tt)* ] $(, $($rest:tt)*)?) => {
+        $kv.insert($key, $crate::bencode!([ $($value)* ]));
+        $crate::bencode!(@dict $kv $($($rest)*)?);
+    };
+    (@dict $kv:ident $key:expr => $value:expr $(, $($rest:tt)*)?) => {
+        if let Some(value) = $crate::bencoding::macros::IntoEntry::into_entry($value) {
+            $kv.insert($key, value);
+        }
+        $crate::bencode!(@dict $kv $($($rest)*)?);
+    };
+
+    (@list [$($done:expr,)*]) => {
+        ::std::vec![$($done),*]
+    };
+    (@list [$($done:expr,)*] { $($value:tt)* } $(, $($rest:tt)*)?) => {
+        $crate::bencode!(@list [$($done,)* $crate::bencode!({ $($value)* }),] $($($rest)*)?)
+    };
+    (@list [$($done:expr,)*] [ $($value:tt)* ] $(, $($rest:tt)*)?) => {
+        $crate::bencode!(@list [$($done,)* $crate::bencode!([ $($value)* ]),] $($($rest)*)?)
+    };
+    (@list [$($done:expr,)*] $value:expr $(, $($rest:tt)*)?) => {
+        $crate::bencode!(@list [$($done,)* $crate::bencoding::value::Value::from($value),] $($($rest)*)?)
+    };
+
+    ({ $($entries:tt)* }) => {{
+        #[allow(unused_mut)]
+        let mut kv = $crate::bencoding::dictionary::Dictionary::new();
+        $crate::bencode!(@dict kv $($entries)*);
+        $crate::bencoding::value::Value::Dictionary(kv)
+    }};
+    ([ $($values:tt)* ]) => {
+        $crate::bencoding::value::Value::List($crate::bencode!(@list [] $($values)*))
+    };
+    () => {
+        $crate::bencode!({})
+    };
+    ($key:expr => $($rest:tt)*) => {
+        $crate::bencode!({ $key => $($rest)* })
+    };
+    ($value:expr) => {
+        $crate::bencoding::value::Value::from($value)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use crate::bencoding::utils::str_to_value;
+    use crate::bencoding::value::Value;
+
+    #[test]
+    fn test_from() {
+        assert_eq!(Value::from(-3), Value::Integer(-3));
+        assert_eq!(Value::from(u64::MAX).to_bytes(), b"i18446744073709551615e");
+        assert_eq!(Value::from("spam"), Value::String(b"spam".to_vec()));
+        assert_eq!(Value::from(vec![0u8, 1]), Value::String(vec![0, 1]));
+        assert_eq!(
+            Value::from(vec!["a", "b"]),
+            str_to_value("l1:a1:be").unwrap()
+        );
+        let map = HashMap::from([("b", 1), ("a", 2)]);
+        assert_eq!(Value::from(map), str_to_value("d1:ai2e1:bi1ee").unwrap());
+        let map = BTreeMap::from([(b"k".to_vec(), vec![1u64])]);
+        assert_eq!(Value::from(map), str_to_value("d1:kli1eee").unwrap());
+    }
+
+    #[test]
+    fn test_macro() {
+        let url = String::from("http://tracker");
+        let comment: Option<&str> = None;
+        let value = bencode! {
+            "announce" => url.clone(),
+            "comment" => comment,
+            "info" => {
+                "files" => [
+                    { "length" => 3u64, "path" => ["dir", "a"] },
+                    { "length" => 4, "path" => vec!["b"] },
+                ],
+                "name" => "root",
+                "pieces" => &[0xffu8; 2],
+                "private" => Some(1),
+            },
+        };
+        assert_eq!(
+            value.to_bytes(),
+            b"d8:announce14:http://tracker4:infod5:filesld6:lengthi3e4:pathl3:dir1:aee\
+              d6:lengthi4e4:pathl1:beee4:name4:root6:pieces2:\xff\xff7:privatei1eee"
+        );
+        assert!(!value.as_dict().unwrap().contains_key("comment"));
+
+        assert_eq!(bencode!(), str_to_value("de").unwrap());
+        assert_eq!(bencode!([]), str_to_value("le").unwrap());
+        assert_eq!(
+            bencode!([1, [2], {}]),
+            str_to_value("li1eli2eedee").unwrap()
+        );
+        assert_eq!(bencode!(7), Value::Integer(7));
+    }
+}
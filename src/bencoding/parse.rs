@@ -1,47 +1,374 @@
+use std::fmt::Display;
+
+use super::dictionary::Dictionary;
+use super::integer::BigInteger;
+use super::path::{format_path, PathSegment};
 use super::value::Value;
 
-#[derive(Debug, PartialEq, Eq)]
-pub enum ParseError {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
     UnsignedIntegerExpected,
     ColonExpected,
     UnexpectedEndOfString,
+    UnexpectedEndOfInput,
     NegativeZeroOccurred,
     IntegerSuffixExpected,
     InvalidPrefix,
     KeyExpectedToBeAString,
     ExpectedDictionaryKey,
+    /// The number does not fit into the integer type.
+    IntegerOverflow,
+    /// Strict mode: a number is written with a leading zero, e.g. `i03e`.
+    LeadingZero,
+    /// Strict mode: a dictionary key is smaller than the previous one.
+    UnsortedKeys,
+    /// Strict mode: a dictionary key appears twice.
+    DuplicateKey,
+    /// Strict mode: there are bytes after the top-level value.
+    TrailingBytes,
+    /// Lists and dictionaries are nested deeper than allowed.
+    MaxDepthExceeded,
+    /// A value is longer than allowed.
+    MaxLengthExceeded,
+    /// A string is longer than `ParseOptions::max_string_length`.
+    MaxStringLengthExceeded,
+    /// The parsed value would take more than `ParseOptions::max_allocation` bytes.
+    MaxAllocationExceeded,
+    /// The input has more values than `ParseOptions::max_elements`.
+    MaxElementsExceeded,
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let description = match self {
+            ParseErrorKind::UnsignedIntegerExpected => "expected a digit",
+            ParseErrorKind::ColonExpected => "expected ':' after string length",
+            ParseErrorKind::UnexpectedEndOfString => "string is shorter than its length prefix",
+            ParseErrorKind::UnexpectedEndOfInput => "unexpected end of input",
+            ParseErrorKind::NegativeZeroOccurred => "negative zero is not allowed",
+            ParseErrorKind::IntegerSuffixExpected => "expected 'e' at the end of integer",
+            ParseErrorKind::InvalidPrefix => "expected one of 'i', 'l', 'd' or a digit",
+            ParseErrorKind::KeyExpectedToBeAString => "dictionary key must be a string",
+            ParseErrorKind::ExpectedDictionaryKey => "expected a dictionary key or 'e'",
+            ParseErrorKind::IntegerOverflow => "number is too large",
+            ParseErrorKind::LeadingZero => "number has a leading zero",
+            ParseErrorKind::UnsortedKeys => "dictionary keys are not sorted",
+            ParseErrorKind::DuplicateKey => "dictionary key is duplicated",
+            ParseErrorKind::TrailingBytes => "unexpected bytes after the value",
+            ParseErrorKind::MaxDepthExceeded => "value is nested too deeply",
+            ParseErrorKind::MaxLengthExceeded => "value is too long",
+            ParseErrorKind::MaxStringLengthExceeded => "string is too long",
+            ParseErrorKind::MaxAllocationExceeded => "value takes too much memory",
+            ParseErrorKind::MaxElementsExceeded => "too many values",
+        };
+        f.write_str(description)
+    }
+}
+
+/// A parse failure together with its location in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    /// Offset of the offending byte, or the length of the input if it ended
+    /// prematurely.
+    pub offset: usize,
+    /// The offending byte, `None` at the end of input.
+    pub byte: Option<u8>,
+    /// Path of the innermost value being parsed, e.g.
+    /// `['info']['files'][3]['path']`.
+    pub path: Vec<PathSegment>,
+}
+
+impl ParseError {
+    pub(crate) fn new(kind: ParseErrorKind, offset: usize, byte: Option<u8>) -> Self {
+        ParseError {
+            kind,
+            offset,
+            byte,
+            path: Vec::new(),
+        }
+    }
+
+    /// Records that the error happened inside `segment`. Segments are
+    /// collected innermost first while unwinding and reversed by
+    /// [`ParseError::finish`].
+    pub(crate) fn within(mut self, segment: PathSegment) -> Self {
+        self.path.push(segment);
+        self
+    }
+
+    pub(crate) fn finish(mut self) -> Self {
+        self.path.reverse();
+        self
+    }
+
+    /// Renders the bytes around the error with a caret under the offending
+    /// byte. Non-printable bytes are shown as '.'.
+    pub fn excerpt(&self, source: &[u8]) -> String {
+        const CONTEXT: usize = 30;
+        let start = self.offset.saturating_sub(CONTEXT);
+        let end = source.len().min(self.offset + CONTEXT);
+        let line = source
+            .get(start..end)
+            .unwrap_or_default()
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect::<String>();
+        format!("{}\n{}^", line, " ".repeat(self.offset - start))
+    }
 }
 
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at offset {}", self.kind, self.offset)?;
+        match self.byte {
+            Some(b) if b.is_ascii_graphic() => write!(f, " (found '{}')", b as char)?,
+            Some(b) => write!(f, " (found byte 0x{:02x})", b)?,
+            None => write!(f, " (found end of input)")?,
+        }
+        if !self.path.is_empty() {
+            write!(f, " in {}", format_path(&self.path))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
 pub type IParseResult<T> = std::result::Result<T, ParseError>;
 
-fn try_parse_value_from_peekable<Bytes>(
-    iter: &mut std::iter::Peekable<Bytes>,
-) -> IParseResult<Value>
+/// Byte range of a value inside the parsed source.
+pub type Span = std::ops::Range<usize>;
+
+/// How the input is validated and how many resources parsing it may take.
+///
+/// The default limits accept any reasonable torrent while keeping hostile
+/// input (e.g. `4294967295:` or a long run of `l`) from exhausting memory or
+/// the stack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Rejects input that is not canonical bencode: numbers with leading
+    /// zeros, unsorted or duplicate dictionary keys and trailing bytes after
+    /// the top-level value. Lenient parsing accepts them, since many
+    /// real-world torrents contain such mistakes.
+    pub strict: bool,
+    /// Maximum nesting of lists and dictionaries.
+    pub max_depth: usize,
+    /// Maximum length of a single string in bytes.
+    pub max_string_length: usize,
+    /// Maximum memory taken by the parsed value: the bytes of all strings
+    /// plus the size of a `Value` for every value.
+    pub max_allocation: usize,
+    /// Maximum number of values, dictionary keys included.
+    pub max_elements: usize,
+    /// Keeps dictionaries in the order their keys appear in the input
+    /// instead of sorting them, so that re-encoding non-canonical input
+    /// reproduces it (duplicate keys aside: the last one wins).
+    pub preserve_order: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            strict: false,
+            max_depth: 128,
+            max_string_length: 64 * 1024 * 1024,
+            max_allocation: 256 * 1024 * 1024,
+            max_elements: 4 * 1024 * 1024,
+            preserve_order: false,
+        }
+    }
+}
+
+impl ParseOptions {
+    pub fn strict() -> Self {
+        ParseOptions {
+            strict: true,
+            ..ParseOptions::default()
+        }
+    }
+
+    pub fn lenient() -> Self {
+        ParseOptions::default()
+    }
+}
+
+/// Resources consumed so far, checked against the limits of [`ParseOptions`].
+#[derive(Debug, Default)]
+pub(crate) struct Usage {
+    depth: usize,
+    elements: usize,
+    allocated: usize,
+}
+
+impl Usage {
+    /// Accounts for a value starting at `offset` with `byte`.
+    pub(crate) fn element(
+        &mut self,
+        options: &ParseOptions,
+        offset: usize,
+        byte: u8,
+    ) -> IParseResult<()> {
+        self.elements += 1;
+        if self.elements > options.max_elements {
+            return Err(ParseError::new(
+                ParseErrorKind::MaxElementsExceeded,
+                offset,
+                Some(byte),
+            ));
+        }
+        self.allocate(options, std::mem::size_of::<Value>(), offset, byte)
+    }
+
+    pub(crate) fn enter(
+        &mut self,
+        options: &ParseOptions,
+        offset: usize,
+        byte: u8,
+    ) -> IParseResult<()> {
+        if self.depth >= options.max_depth {
+            return Err(ParseError::new(
+                ParseErrorKind::MaxDepthExceeded,
+                offset,
+                Some(byte),
+            ));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    pub(crate) fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    /// Accounts for a string of `len` bytes whose length prefix starts at
+    /// `offset` with `byte`.
+    pub(crate) fn string(
+        &mut self,
+        options: &ParseOptions,
+        len: u64,
+        offset: usize,
+        byte: u8,
+    ) -> IParseResult<()> {
+        if len > options.max_string_length as u64 {
+            return Err(ParseError::new(
+                ParseErrorKind::MaxStringLengthExceeded,
+                offset,
+                Some(byte),
+            ));
+        }
+        self.allocate(options, len as usize, offset, byte)
+    }
+
+    fn allocate(
+        &mut self,
+        options: &ParseOptions,
+        bytes: usize,
+        offset: usize,
+        byte: u8,
+    ) -> IParseResult<()> {
+        self.allocated = self.allocated.saturating_add(bytes);
+        if self.allocated > options.max_allocation {
+            return Err(ParseError::new(
+                ParseErrorKind::MaxAllocationExceeded,
+                offset,
+                Some(byte),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Input of the parser that keeps track of the current byte offset.
+struct Source<Bytes: Iterator<Item = u8>> {
+    iter: std::iter::Peekable<Bytes>,
+    pos: usize,
+    options: ParseOptions,
+    usage: Usage,
+}
+
+impl<Bytes: Iterator<Item = u8>> Source<Bytes> {
+    fn new(bytes: Bytes, options: ParseOptions) -> Self {
+        Source {
+            iter: bytes.peekable(),
+            pos: 0,
+            options,
+            usage: Usage::default(),
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.iter.peek().copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let b = self.iter.next();
+        if b.is_some() {
+            self.pos += 1;
+        }
+        b
+    }
+
+    /// Creates an error pointing at the next (not yet consumed) byte.
+    fn error(&mut self, kind: ParseErrorKind) -> ParseError {
+        ParseError::new(kind, self.pos, self.peek())
+    }
+}
+
+fn try_parse_value_from_peekable<Bytes>(iter: &mut Source<Bytes>) -> IParseResult<Value>
 where
     Bytes: Iterator<Item = u8>,
 {
-    let prefix = iter.peek();
-    match prefix {
-        Some(b) => match b {
-            b'i' => parse_integer(iter),
-            b'l' => parse_list(iter),
-            b'd' => parse_dictionary(iter),
-            b'0'..=b'9' => parse_string(iter),
-            _ => Err(ParseError::InvalidPrefix),
-        },
-        None => unreachable!(),
-    }
-}
-
-fn parse_unsigned_integer<Bytes: Iterator<Item = u8>>(
-    it: &mut std::iter::Peekable<Bytes>,
-) -> IParseResult<u64> {
+    let offset = iter.pos;
+    match iter.peek() {
+        Some(b) => iter.usage.element(&iter.options, offset, b)?,
+        None => return Err(iter.error(ParseErrorKind::UnexpectedEndOfInput)),
+    }
+    match iter.peek() {
+        Some(b'i') => parse_integer(iter),
+        Some(b @ (b'l' | b'd')) => {
+            iter.usage.enter(&iter.options, offset, b)?;
+            let value = if b == b'l' {
+                parse_list(iter)
+            } else {
+                parse_dictionary(iter)
+            };
+            iter.usage.leave();
+            value
+        }
+        Some(b'0'..=b'9') => parse_string(iter),
+        _ => Err(iter.error(ParseErrorKind::InvalidPrefix)),
+    }
+}
+
+/// Strings are read in chunks of at most this size so that a forged length
+/// prefix cannot make the parser allocate more than the input holds.
+pub(crate) const STRING_CHUNK: usize = 64 * 1024;
+
+fn parse_unsigned_integer<Bytes: Iterator<Item = u8>>(it: &mut Source<Bytes>) -> IParseResult<u64> {
+    let start = it.pos;
     let mut num: u64 = 0;
     let mut first = false;
     while let Some(b) = it.peek() {
         if b.is_ascii_digit() {
+            if it.options.strict && first && num == 0 {
+                return Err(ParseError::new(
+                    ParseErrorKind::LeadingZero,
+                    start,
+                    Some(b'0'),
+                ));
+            }
             first = true;
-            num = num * 10 + (b - b'0') as u64;
+            num = num
+                .checked_mul(10)
+                .and_then(|num| num.checked_add((b - b'0') as u64))
+                .ok_or_else(|| ParseError::new(ParseErrorKind::IntegerOverflow, start, None))?;
             it.next();
         } else {
             break;
@@ -50,100 +377,296 @@ fn parse_unsigned_integer<Bytes: Iterator<Item = u8>>(
     if first {
         Ok(num)
     } else {
-        Err(ParseError::UnsignedIntegerExpected)
+        Err(it.error(ParseErrorKind::UnsignedIntegerExpected))
     }
 }
-fn parse_string<Bytes: Iterator<Item = u8>>(
-    it: &mut std::iter::Peekable<Bytes>,
-) -> IParseResult<Value> {
+fn parse_string<Bytes: Iterator<Item = u8>>(it: &mut Source<Bytes>) -> IParseResult<Value> {
+    parse_bytes(it).map(Value::String)
+}
+
+fn parse_bytes<Bytes: Iterator<Item = u8>>(it: &mut Source<Bytes>) -> IParseResult<Vec<u8>> {
+    let start = it.pos;
+    let first = it.peek().unwrap_or_default();
     let len = parse_unsigned_integer(it)?;
-    match it.next() {
+    it.usage.string(&it.options, len, start, first)?;
+    let len = len as usize;
+    match it.peek() {
         Some(b':') => {
-            let mut bytes = Vec::with_capacity(len as usize);
+            it.next();
+            let mut bytes = Vec::with_capacity(len.min(STRING_CHUNK));
             for _ in 0..len {
                 match it.next() {
                     Some(byte) => bytes.push(byte),
-                    None => return Err(ParseError::UnexpectedEndOfString),
+                    None => return Err(it.error(ParseErrorKind::UnexpectedEndOfString)),
                 };
             }
-            Ok(Value::String(bytes))
+            Ok(bytes)
         }
-        Some(_) => Err(ParseError::ColonExpected),
-        None => Err(ParseError::ColonExpected),
+        _ => Err(it.error(ParseErrorKind::ColonExpected)),
     }
 }
 
-fn parse_dictionary<Bytes: Iterator<Item = u8>>(
-    it: &mut std::iter::Peekable<Bytes>,
-) -> IParseResult<Value> {
+fn parse_dictionary<Bytes: Iterator<Item = u8>>(it: &mut Source<Bytes>) -> IParseResult<Value> {
     it.next();
-    let mut dict = Vec::new();
+    // Entries are sorted once at the end: inserting them one by one would
+    // be quadratic for unsorted input.
+    let mut entries: Vec<(Vec<u8>, Value)> = Vec::new();
     loop {
         match it.peek() {
             Some(b'e') => {
                 it.next();
+                let dict = Dictionary::from_entries(entries, it.options.preserve_order);
                 break Ok(Value::Dictionary(dict));
             }
-            Some(_) => {
-                let key = try_parse_value_from_peekable(it)?;
-                let value = try_parse_value_from_peekable(it)?;
-                match key {
-                    Value::String(_) => {}
-                    _ => return Err(ParseError::KeyExpectedToBeAString),
+            Some(key_byte @ b'0'..=b'9') => {
+                let key_offset = it.pos;
+                it.usage.element(&it.options, key_offset, key_byte)?;
+                let key = parse_bytes(it)?;
+                if it.options.strict {
+                    // Keys must increase, so comparing with the previous
+                    // one is enough.
+                    if let Some((previous, _)) = entries.last() {
+                        let kind = match key.cmp(previous) {
+                            std::cmp::Ordering::Less => Some(ParseErrorKind::UnsortedKeys),
+                            std::cmp::Ordering::Equal => Some(ParseErrorKind::DuplicateKey),
+                            std::cmp::Ordering::Greater => None,
+                        };
+                        if let Some(kind) = kind {
+                            return Err(ParseError::new(kind, key_offset, Some(key_byte))
+                                .within(key_segment(&key)));
+                        }
+                    }
                 }
-                dict.push((key, value));
+                let value = match try_parse_value_from_peekable(it) {
+                    Ok(value) => value,
+                    Err(e) => return Err(e.within(key_segment(&key))),
+                };
+                entries.push((key, value));
             }
-            None => return Err(ParseError::ExpectedDictionaryKey),
+            Some(_) => return Err(it.error(ParseErrorKind::KeyExpectedToBeAString)),
+            None => return Err(it.error(ParseErrorKind::ExpectedDictionaryKey)),
         }
     }
 }
-fn parse_list<Bytes: Iterator<Item = u8>>(
-    it: &mut std::iter::Peekable<Bytes>,
-) -> IParseResult<Value> {
+
+fn key_segment(key: &[u8]) -> PathSegment {
+    PathSegment::Key(key.to_vec())
+}
+
+fn parse_list<Bytes: Iterator<Item = u8>>(it: &mut Source<Bytes>) -> IParseResult<Value> {
     it.next();
     let mut list = Vec::new();
-    while let Some(e) = it.peek() {
-        if *e == b'e' {
-            it.next();
-            break;
-        } else {
-            list.push(try_parse_value_from_peekable(it)?);
+    loop {
+        match it.peek() {
+            Some(b'e') => {
+                it.next();
+                break Ok(Value::List(list));
+            }
+            Some(_) => match try_parse_value_from_peekable(it) {
+                Ok(value) => list.push(value),
+                Err(e) => return Err(e.within(PathSegment::Index(list.len()))),
+            },
+            None => return Err(it.error(ParseErrorKind::UnexpectedEndOfInput)),
         }
     }
-
-    Ok(Value::List(list))
 }
 
-fn parse_integer<Bytes: Iterator<Item = u8>>(
-    it: &mut std::iter::Peekable<Bytes>,
-) -> IParseResult<Value> {
+fn parse_integer<Bytes: Iterator<Item = u8>>(it: &mut Source<Bytes>) -> IParseResult<Value> {
     it.next();
-    let sign: i8 = match it.peek() {
-        Some(b'-') => {
-            it.next();
-            -1
+    let sign_offset = it.pos;
+    let negative = it.peek() == Some(b'-');
+    if negative {
+        it.next();
+    }
+
+    let start = it.pos;
+    let first = it.peek().unwrap_or_default();
+    let mut digits = 0;
+    let mut magnitude: u64 = 0;
+    // All digits once the magnitude no longer fits into u64.
+    let mut big = Vec::new();
+    while let Some(b @ b'0'..=b'9') = it.peek() {
+        if it.options.strict && digits == 1 && first == b'0' {
+            return Err(ParseError::new(
+                ParseErrorKind::LeadingZero,
+                start,
+                Some(b'0'),
+            ));
         }
-        _ => 1,
-    };
-    let unsigned = parse_unsigned_integer(it)?;
+        if digits == it.options.max_string_length {
+            return Err(ParseError::new(
+                ParseErrorKind::MaxStringLengthExceeded,
+                start,
+                Some(first),
+            ));
+        }
+        if big.is_empty() {
+            match magnitude
+                .checked_mul(10)
+                .and_then(|m| m.checked_add((b - b'0') as u64))
+            {
+                Some(m) => magnitude = m,
+                None => big = format!("{}{}", magnitude, b as char).into_bytes(),
+            }
+        } else {
+            big.push(b);
+        }
+        digits += 1;
+        it.next();
+    }
+    if digits == 0 {
+        return Err(it.error(ParseErrorKind::UnsignedIntegerExpected));
+    }
 
-    if unsigned == 0 && sign == -1 {
-        return Err(ParseError::NegativeZeroOccurred);
+    if negative && magnitude == 0 && big.is_empty() {
+        return Err(ParseError::new(
+            ParseErrorKind::NegativeZeroOccurred,
+            sign_offset,
+            Some(b'-'),
+        ));
     }
 
+    let value = if big.is_empty() {
+        match apply_sign(negative, magnitude) {
+            Some(i) => Value::Integer(i),
+            None => integer_from_digits(negative, magnitude.to_string().as_bytes()),
+        }
+    } else {
+        it.usage
+            .string(&it.options, big.len() as u64, start, first)?;
+        integer_from_digits(negative, &big)
+    };
+
     match it.peek() {
         Some(b'e') => {
             it.next();
-            Ok(Value::Integer(sign as i64 * unsigned as i64))
+            Ok(value)
         }
-        Some(_) => Err(ParseError::IntegerSuffixExpected),
-        None => Err(ParseError::IntegerSuffixExpected),
+        _ => Err(it.error(ParseErrorKind::IntegerSuffixExpected)),
+    }
+}
+
+/// The value of an integer given its sign and decimal digits, which may have
+/// leading zeros: [`Value::Integer`] when it fits, [`Value::BigInteger`]
+/// otherwise.
+pub(crate) fn integer_from_digits(negative: bool, digits: &[u8]) -> Value {
+    let magnitude = digits.iter().try_fold(0u64, |m, b| {
+        m.checked_mul(10)?.checked_add((b - b'0') as u64)
+    });
+    match magnitude.and_then(|m| apply_sign(negative, m)) {
+        Some(i) => Value::Integer(i),
+        None => Value::BigInteger(BigInteger::from_digits(negative, digits)),
+    }
+}
+
+/// Converts a parsed magnitude into `i64`, `None` if it does not fit.
+pub(crate) fn apply_sign(negative: bool, unsigned: u64) -> Option<i64> {
+    if negative {
+        // -2^63 is the only value whose magnitude does not fit into i64.
+        if unsigned <= i64::MIN.unsigned_abs() {
+            Some((unsigned as i64).wrapping_neg())
+        } else {
+            None
+        }
+    } else {
+        i64::try_from(unsigned).ok()
     }
 }
 
 pub fn try_parse_value<T: Iterator<Item = u8>>(source: T) -> IParseResult<Value> {
-    let mut iter = source.into_iter().peekable();
-    try_parse_value_from_peekable(&mut iter)
+    try_parse_value_with_options(source, ParseOptions::default())
+}
+
+pub fn try_parse_value_with_options<T: Iterator<Item = u8>>(
+    source: T,
+    options: ParseOptions,
+) -> IParseResult<Value> {
+    let mut iter = Source::new(source, options);
+    let value = try_parse_value_from_peekable(&mut iter).map_err(ParseError::finish)?;
+    if iter.options.strict && iter.peek().is_some() {
+        return Err(iter.error(ParseErrorKind::TrailingBytes));
+    }
+    Ok(value)
+}
+
+/// The span of the child at `segment` of the list or dictionary at the
+/// start of `source`. The container is parsed to its end, so a
+/// key occurring several times resolves to its last value, as it does in
+/// the parsed [`Dictionary`].
+fn child_span(source: &[u8], segment: &PathSegment) -> IParseResult<Option<Span>> {
+    let mut it = Source::new(source.iter().copied(), ParseOptions::default());
+    match (it.peek(), segment) {
+        (Some(b'd'), PathSegment::Key(wanted)) => {
+            it.next();
+            let mut found = None;
+            loop {
+                match it.peek() {
+                    Some(b'e') => return Ok(found),
+                    Some(b'0'..=b'9') => {
+                        let key = parse_bytes(&mut it)?;
+                        let start = it.pos;
+                        try_parse_value_from_peekable(&mut it)
+                            .map_err(|e| e.within(key_segment(&key)))?;
+                        if &key == wanted {
+                            found = Some(start..it.pos);
+                        }
+                    }
+                    Some(_) => return Err(it.error(ParseErrorKind::KeyExpectedToBeAString)),
+                    None => return Err(it.error(ParseErrorKind::ExpectedDictionaryKey)),
+                }
+            }
+        }
+        (Some(b'l'), PathSegment::Index(wanted)) => {
+            it.next();
+            for ind in 0.. {
+                match it.peek() {
+                    Some(b'e') => break,
+                    Some(_) => {
+                        let start = it.pos;
+                        try_parse_value_from_peekable(&mut it)
+                            .map_err(|e| e.within(PathSegment::Index(ind)))?;
+                        if ind == *wanted {
+                            return Ok(Some(start..it.pos));
+                        }
+                    }
+                    None => return Err(it.error(ParseErrorKind::UnexpectedEndOfInput)),
+                }
+            }
+            Ok(None)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Returns the byte span of the sub-value located at `path`, or `None` if
+/// there is no such value.
+///
+/// The span covers the value exactly as it appears in `source`, which is
+/// what infohashes must be computed over.
+///
+/// A dictionary key that occurs more than once resolves to its last value,
+/// the one the parsers keep.
+pub fn find_span(source: &[u8], path: &[PathSegment]) -> IParseResult<Option<Span>> {
+    if path.is_empty() {
+        let mut it = Source::new(source.iter().copied(), ParseOptions::default());
+        try_parse_value_from_peekable(&mut it).map_err(ParseError::finish)?;
+        return Ok(Some(0..it.pos));
+    }
+    let mut span = 0..source.len();
+    for (depth, segment) in path.iter().enumerate() {
+        match child_span(&source[span.start..], segment) {
+            Ok(Some(child)) => span = span.start + child.start..span.start + child.end,
+            Ok(None) => return Ok(None),
+            Err(mut e) => {
+                e.offset += span.start;
+                for parent in path[..depth].iter().rev() {
+                    e = e.within(parent.clone());
+                }
+                return Err(e.finish());
+            }
+        }
+    }
+    Ok(Some(span))
 }
 
 #[cfg(test)]
@@ -156,19 +679,18 @@ mod tests {
     fn test_parsing_of_string() {
         assert_eq!(
             str_to_value("4:spam"),
-            Ok(Value::String(
-                "spam".bytes().into_iter().collect::<Vec<u8>>()
-            ))
+            Ok(Value::String("spam".bytes().collect::<Vec<u8>>()))
         )
     }
     #[test]
     fn test_parsing_of_integer() {
         assert_eq!(str_to_value("i0e"), Ok(Value::Integer(0)));
-        assert_eq!(str_to_value("i-0e"), Err(ParseError::NegativeZeroOccurred));
+        let kind = |s: &str| str_to_value(s).unwrap_err().kind;
+        assert_eq!(kind("i-0e"), ParseErrorKind::NegativeZeroOccurred);
         assert_eq!(str_to_value("i-42e"), Ok(Value::Integer(-42)));
         assert_eq!(str_to_value("i42e"), Ok(Value::Integer(42)));
-        assert_eq!(str_to_value("i42"), Err(ParseError::IntegerSuffixExpected));
-        assert_eq!(str_to_value("i42:"), Err(ParseError::IntegerSuffixExpected));
+        assert_eq!(kind("i42"), ParseErrorKind::IntegerSuffixExpected);
+        assert_eq!(kind("i42:"), ParseErrorKind::IntegerSuffixExpected);
     }
 
     #[test]
@@ -178,12 +700,211 @@ mod tests {
             Ok(Value::List(
                 ["spam", "eggs"]
                     .into_iter()
-                    .map(|str| Value::String(str.bytes().into_iter().collect::<Vec<u8>>()))
+                    .map(|str| Value::String(str.bytes().collect::<Vec<u8>>()))
                     .collect::<Vec<_>>()
             ))
         )
     }
 
+    #[test]
+    fn test_error_location() {
+        let err = str_to_value("d4:infod5:filesld4:pathl1:a1xeeeee").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::ColonExpected);
+        assert_eq!(err.offset, 28);
+        assert_eq!(err.byte, Some(b'x'));
+        assert_eq!(format_path(&err.path), "['info']['files'][0]['path'][1]");
+        assert_eq!(
+            err.to_string(),
+            "expected ':' after string length at offset 28 (found 'x') in ['info']['files'][0]['path'][1]"
+        );
+        assert_eq!(
+            err.excerpt(b"d4:infod5:filesld4:pathl1:a1xeeeee"),
+            "d4:infod5:filesld4:pathl1:a1xeeeee\n                            ^"
+        );
+
+        let err = str_to_value("l4:spam").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnexpectedEndOfInput);
+        assert_eq!((err.offset, err.byte), (7, None));
+        assert_eq!(
+            err.to_string(),
+            "unexpected end of input at offset 7 (found end of input)"
+        );
+
+        let err = str_to_value("d1:ai1e2:xx").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnexpectedEndOfInput);
+        assert_eq!(format_path(&err.path), "['xx']");
+
+        let err = str_to_value("di1ei2ee").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::KeyExpectedToBeAString);
+        assert_eq!((err.offset, err.byte), (1, Some(b'i')));
+
+        let err = str_to_value("li-0ee").unwrap_err();
+        assert_eq!((err.offset, err.byte), (2, Some(b'-')));
+        assert_eq!(
+            str_to_value("").unwrap_err().kind,
+            ParseErrorKind::UnexpectedEndOfInput
+        );
+        assert_eq!(
+            str_to_value("x").unwrap_err().kind,
+            ParseErrorKind::InvalidPrefix
+        );
+    }
+
+    #[test]
+    fn test_strict_mode() {
+        let strict = |s: &str| try_parse_value_with_options(s.bytes(), ParseOptions::strict());
+        let kind = |s: &str| strict(s).unwrap_err().kind;
+
+        assert_eq!(
+            strict("d3:cow3:moo4:spami0ee"),
+            str_to_value("d3:cow3:moo4:spami0ee")
+        );
+        assert_eq!(strict("i-10e"), Ok(Value::Integer(-10)));
+
+        assert_eq!(kind("i03e"), ParseErrorKind::LeadingZero);
+        assert_eq!(kind("i-03e"), ParseErrorKind::LeadingZero);
+        assert_eq!(kind("04:spam"), ParseErrorKind::LeadingZero);
+        assert_eq!(kind("d4:spami1e3:cowi2ee"), ParseErrorKind::UnsortedKeys);
+        assert_eq!(kind("d3:cowi1e3:cowi2ee"), ParseErrorKind::DuplicateKey);
+        assert_eq!(kind("i1ei2e"), ParseErrorKind::TrailingBytes);
+
+        let err = strict("d1:ad1:bi1e1:ai2eee").unwrap_err();
+        assert_eq!((err.offset, err.byte), (11, Some(b'1')));
+        assert_eq!(format_path(&err.path), "['a']['a']");
+
+        assert_eq!(str_to_value("i03e"), Ok(Value::Integer(3)));
+        assert!(str_to_value("d3:cowi1e3:cowi2ee").is_ok());
+        assert!(str_to_value("i1ei2e").is_ok());
+    }
+
+    #[test]
+    fn test_hostile_input() {
+        let kind = |source: &[u8], options: ParseOptions| {
+            try_parse_value_with_options(source.iter().copied(), options)
+                .unwrap_err()
+                .kind
+        };
+        let default = ParseOptions::default;
+
+        // A 20 byte string claiming to be 4 GiB long.
+        assert_eq!(
+            kind(b"4294967295:aaaaaaaaa", default()),
+            ParseErrorKind::MaxStringLengthExceeded
+        );
+        assert_eq!(
+            kind(
+                b"4294967295:aaaaaaaaa",
+                ParseOptions {
+                    max_string_length: usize::MAX,
+                    max_allocation: usize::MAX,
+                    ..default()
+                }
+            ),
+            ParseErrorKind::UnexpectedEndOfString
+        );
+
+        let nested = vec![b'l'; 1_000_000];
+        let err = try_parse_value_with_options(nested.iter().copied(), default()).unwrap_err();
+        assert_eq!(
+            (err.kind, err.offset),
+            (ParseErrorKind::MaxDepthExceeded, 128)
+        );
+        assert_eq!(err.path.len(), 128);
+
+        let strings = b"l"
+            .iter()
+            .chain(b"1:a".repeat(100).iter())
+            .chain(b"e")
+            .copied()
+            .collect::<Vec<_>>();
+        let options = ParseOptions {
+            max_elements: 50,
+            ..default()
+        };
+        assert_eq!(kind(&strings, options), ParseErrorKind::MaxElementsExceeded);
+        let options = ParseOptions {
+            max_allocation: 10 * std::mem::size_of::<Value>(),
+            ..default()
+        };
+        assert_eq!(
+            kind(&strings, options),
+            ParseErrorKind::MaxAllocationExceeded
+        );
+        let options = ParseOptions {
+            max_string_length: 3,
+            ..default()
+        };
+        assert_eq!(
+            kind(b"d3:key4:spame", options),
+            ParseErrorKind::MaxStringLengthExceeded
+        );
+        assert!(try_parse_value_with_options(strings.iter().copied(), default()).is_ok());
+    }
+
+    #[test]
+    fn test_big_integers() {
+        assert_eq!(
+            str_to_value("i9223372036854775807e"),
+            Ok(Value::Integer(i64::MAX))
+        );
+        assert_eq!(
+            str_to_value("i-9223372036854775808e"),
+            Ok(Value::Integer(i64::MIN))
+        );
+        for big in [
+            "9223372036854775808",
+            "-9223372036854775809",
+            "18446744073709551616",
+            "-123456789012345678901234567890",
+        ] {
+            let value = str_to_value(&format!("i{}e", big)).unwrap();
+            assert_eq!(value, Value::BigInteger(big.parse().unwrap()));
+            assert_eq!(value.to_bytes(), format!("i{}e", big).as_bytes());
+        }
+        assert_eq!(
+            str_to_value("i-00000000000000000000000000042e"),
+            Ok(Value::Integer(-42))
+        );
+        assert_eq!(
+            str_to_value("i000000000000000000000000018446744073709551616e"),
+            Ok(Value::BigInteger("18446744073709551616".parse().unwrap()))
+        );
+        assert_eq!(
+            str_to_value("i-00000000000000000000000e").unwrap_err().kind,
+            ParseErrorKind::NegativeZeroOccurred
+        );
+        assert_eq!(
+            str_to_value("99999999999999999999999:a").unwrap_err().kind,
+            ParseErrorKind::IntegerOverflow
+        );
+    }
+
+    #[test]
+    fn test_find_span() {
+        let source = b"d4:infod1:ai03ee4:listl1:xi2eee";
+        let span = |path: &[PathSegment]| find_span(source, path).unwrap();
+
+        assert_eq!(span(&[]), Some(0..source.len()));
+        assert_eq!(span(&["info".into()]), Some(7..16));
+        assert_eq!(&source[7..16], b"d1:ai03ee");
+        assert_eq!(span(&["info".into(), "a".into()]), Some(11..15));
+        assert_eq!(span(&["list".into(), 1.into()]), Some(26..29));
+        assert_eq!(span(&["list".into(), 2.into()]), None);
+        assert_eq!(span(&["missing".into()]), None);
+        assert_eq!(span(&["info".into(), 0.into()]), None);
+
+        // The last of duplicate keys, as in the parsed dictionary.
+        let source = b"d1:ai1e1:bi2e1:ai3ee";
+        assert_eq!(find_span(source, &["a".into()]).unwrap(), Some(16..19));
+
+        let err = find_span(b"d4:infod1:ali1x", &["info".into(), "a".into()]).unwrap_err();
+        assert_eq!(
+            (err.kind, err.offset),
+            (ParseErrorKind::IntegerSuffixExpected, 14)
+        );
+        assert_eq!(format_path(&err.path), "['info']['a'][0]");
+    }
+
     #[test]
     fn test_parsing_of_dictionaries() {
         assert_eq!(
@@ -191,14 +912,66 @@ mod tests {
             Ok(Value::Dictionary(
                 [("cow", "moo"), ("spam", "eggs"),]
                     .into_iter()
-                    .map(|(k, v)| {
-                        (
-                            Value::String(k.bytes().into_iter().collect::<Vec<u8>>()),
-                            Value::String(v.bytes().into_iter().collect::<Vec<u8>>()),
-                        )
-                    })
-                    .collect::<Vec<_>>()
+                    .map(|(k, v)| (k, Value::String(v.bytes().collect::<Vec<u8>>())))
+                    .collect::<Dictionary>()
             ))
         )
     }
+
+    #[test]
+    fn test_dictionary_order() {
+        let source = b"d4:spami1e3:cowi2e4:spami3ee";
+        let parse = |options| try_parse_value_with_options(source.iter().copied(), options);
+
+        let sorted = parse(ParseOptions::lenient()).unwrap();
+        assert_eq!(sorted.to_bytes(), b"d3:cowi2e4:spami3ee");
+
+        let options = ParseOptions {
+            preserve_order: true,
+            ..ParseOptions::lenient()
+        };
+        let preserved = parse(options).unwrap();
+        assert_eq!(preserved.to_bytes(), b"d4:spami3e3:cowi2ee");
+        assert_eq!(preserved["cow"], Value::Integer(2));
+
+        let source = b"d4:spami1e3:cowi2ee";
+        let options = ParseOptions {
+            preserve_order: true,
+            ..ParseOptions::lenient()
+        };
+        let value = try_parse_value_with_options(source.iter().copied(), options).unwrap();
+        assert_eq!(value.to_bytes(), source);
+    }
+
+    #[test]
+    fn test_unsorted_dictionary_is_not_quadratic() {
+        // Keys in reverse order made every insertion shift all the entries
+        // before, taking minutes for a few megabytes of input.
+        let count = 200_000;
+        let mut source = b"d".to_vec();
+        for i in (0..count).rev() {
+            let key = format!("{:08}", i);
+            source.extend(format!("{}:{}i0e", key.len(), key).bytes());
+        }
+        source.push(b'e');
+        for preserve_order in [false, true] {
+            let options = ParseOptions {
+                preserve_order,
+                ..ParseOptions::lenient()
+            };
+            let value = try_parse_value_with_options(source.iter().copied(), options).unwrap();
+            let dict = match &value {
+                Value::Dictionary(dict) => dict,
+                _ => panic!("expected a dictionary"),
+            };
+            assert_eq!(dict.len(), count);
+            let first = if preserve_order {
+                b"00199999"
+            } else {
+                b"00000000"
+            };
+            assert_eq!(dict.keys().next(), Some(&first[..]));
+            assert!(dict.contains_key("00123456"));
+        }
+    }
 }
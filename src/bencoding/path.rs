@@ -0,0 +1,52 @@
+use std::fmt::Display;
+
+/// One step from a list or dictionary into one of its children.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Key(Vec<u8>),
+    Index(usize),
+}
+
+/// Prints the segment as a query selector: keys are quoted, with `'` and `\`
+/// escaped and control characters and invalid UTF-8 written as `\xNN`.
+impl Display for PathSegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathSegment::Key(key) => {
+                write!(f, "['")?;
+                for chunk in key.utf8_chunks() {
+                    for c in chunk.valid().chars() {
+                        match c {
+                            '\'' | '\\' => write!(f, "\\{}", c)?,
+                            c if c.is_ascii_control() => write!(f, "\\x{:02x}", c as u8)?,
+                            c => write!(f, "{}", c)?,
+                        }
+                    }
+                    for b in chunk.invalid() {
+                        write!(f, "\\x{:02x}", b)?;
+                    }
+                }
+                write!(f, "']")
+            }
+            PathSegment::Index(ind) => write!(f, "[{}]", ind),
+        }
+    }
+}
+
+impl From<&str> for PathSegment {
+    fn from(key: &str) -> Self {
+        PathSegment::Key(key.as_bytes().to_vec())
+    }
+}
+
+impl From<usize> for PathSegment {
+    fn from(ind: usize) -> Self {
+        PathSegment::Index(ind)
+    }
+}
+
+/// Formats a path the same way `print_torrent` prefixes values, e.g.
+/// `['info']['files'][3]['path']`.
+pub fn format_path(path: &[PathSegment]) -> String {
+    path.iter().map(|segment| segment.to_string()).collect()
+}
@@ -0,0 +1,322 @@
+use std::io::Write;
+
+use super::path::{format_path, PathSegment};
+use super::utils::{to_base64, to_hex};
+use super::value::Value;
+
+/// How strings that are not valid UTF-8 are shown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BinaryRendering {
+    /// `0x` followed by lowercase hex digits.
+    #[default]
+    Hex,
+    /// `base64:` followed by standard padded base64.
+    Base64,
+    /// Quoted like text, with invalid sequences replaced by U+FFFD.
+    Lossy,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Layout {
+    /// One line per scalar, prefixed by its full path:
+    /// `['info']['files'][0]['length'] = 3`.
+    #[default]
+    Flat,
+    /// One line per entry, indented under its parent like tree(1).
+    Tree,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrintOptions {
+    /// Strings longer than this many characters are shortened to their
+    /// beginning and end. `None` prints them whole.
+    pub max_string_length: Option<usize>,
+    pub binary: BinaryRendering,
+    /// Lists and dictionaries this deep are summarized instead of expanded;
+    /// the value printed is at depth 0.
+    pub max_depth: Option<usize>,
+    pub layout: Layout,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        PrintOptions {
+            max_string_length: Some(54),
+            binary: BinaryRendering::default(),
+            max_depth: None,
+            layout: Layout::default(),
+        }
+    }
+}
+
+pub fn pretty_print(value: &Value, w: &mut impl Write) -> std::io::Result<()> {
+    pretty_print_with_options(value, w, &PrintOptions::default())
+}
+
+pub fn pretty_print_with_options(
+    value: &Value,
+    w: &mut impl Write,
+    options: &PrintOptions,
+) -> std::io::Result<()> {
+    pretty_print_at(value, &[], w, options)
+}
+
+/// Prints `value` as found at `path`, which prefixes (or, in a tree, heads)
+/// everything printed.
+pub fn pretty_print_at(
+    value: &Value,
+    path: &[PathSegment],
+    w: &mut impl Write,
+    options: &PrintOptions,
+) -> std::io::Result<()> {
+    match options.layout {
+        Layout::Flat => print_flat(value, &mut path.to_vec(), 0, w, options),
+        Layout::Tree => {
+            let children = expand(value, 0, options);
+            match (path.is_empty(), &children) {
+                (true, Some(_)) => {}
+                (true, None) => writeln!(w, "{}", describe_value(value, options))?,
+                (false, Some(_)) => writeln!(w, "{}", format_path(path))?,
+                (false, None) => writeln!(
+                    w,
+                    "{} = {}",
+                    format_path(path),
+                    describe_value(value, options)
+                )?,
+            }
+            match children {
+                Some(children) => print_children(&children, "", 1, w, options),
+                None => Ok(()),
+            }
+        }
+    }
+}
+
+/// A one-line rendering of a value: scalars as printed, lists and
+/// dictionaries by their size.
+pub fn describe_value(value: &Value, options: &PrintOptions) -> String {
+    match value {
+        Value::String(bytes) => match (std::str::from_utf8(bytes), options.binary) {
+            (Ok(s), _) => format!("'{}'", shorten(s, options)),
+            (Err(_), BinaryRendering::Hex) => format!("0x{}", shorten(&to_hex(bytes), options)),
+            (Err(_), BinaryRendering::Base64) => {
+                format!("base64:{}", shorten(&to_base64(bytes), options))
+            }
+            (Err(_), BinaryRendering::Lossy) => {
+                format!("'{}'", shorten(&String::from_utf8_lossy(bytes), options))
+            }
+        },
+        Value::Integer(n) => n.to_string(),
+        Value::BigInteger(n) => n.to_string(),
+        Value::List(values) => format!("list of {}", values.len()),
+        Value::Dictionary(kv) => format!("dictionary of {}", kv.len()),
+    }
+}
+
+/// Keeps the start and end of `s` around a marker so that the result has
+/// at most `max_string_length` chars. Strings are left alone when the limit
+/// is too small to keep anything.
+fn shorten(s: &str, options: &PrintOptions) -> String {
+    let chars = s.chars().count();
+    // "...N chars...", with the omitted count having at most as many digits
+    // as `chars`.
+    let marker = 12 + chars.to_string().len();
+    match options.max_string_length {
+        Some(max) if chars > max && max >= marker + 2 => {
+            let keep = (max - marker) / 2;
+            let start = s.chars().take(keep).collect::<String>();
+            let end = s.chars().skip(chars - keep).collect::<String>();
+            format!("{}...{} chars...{}", start, chars - 2 * keep, end)
+        }
+        _ => s.to_owned(),
+    }
+}
+
+/// The children to print below `value`, or `None` when it is printed on
+/// one line: scalars, empty containers and containers at `max_depth`.
+fn expand<'v>(
+    value: &'v Value,
+    depth: usize,
+    options: &PrintOptions,
+) -> Option<Vec<(PathSegment, &'v Value)>> {
+    if options.max_depth.is_some_and(|max| depth >= max) {
+        return None;
+    }
+    let children = match value {
+        Value::List(values) => values
+            .iter()
+            .enumerate()
+            .map(|(ind, v)| (PathSegment::Index(ind), v))
+            .collect::<Vec<_>>(),
+        Value::Dictionary(kv) => kv
+            .iter()
+            .map(|(k, v)| (PathSegment::Key(k.to_vec()), v))
+            .collect(),
+        _ => return None,
+    };
+    (!children.is_empty()).then_some(children)
+}
+
+fn print_flat(
+    value: &Value,
+    path: &mut Vec<PathSegment>,
+    depth: usize,
+    w: &mut impl Write,
+    options: &PrintOptions,
+) -> std::io::Result<()> {
+    match expand(value, depth, options) {
+        Some(children) => {
+            for (segment, child) in children {
+                path.push(segment);
+                print_flat(child, path, depth + 1, w, options)?;
+                path.pop();
+            }
+            Ok(())
+        }
+        None if path.is_empty() => writeln!(w, "{}", describe_value(value, options)),
+        None => writeln!(
+            w,
+            "{} = {}",
+            format_path(path),
+            describe_value(value, options)
+        ),
+    }
+}
+
+fn print_tree(
+    value: &Value,
+    segment: &PathSegment,
+    indent: &str,
+    last: bool,
+    depth: usize,
+    w: &mut impl Write,
+    options: &PrintOptions,
+) -> std::io::Result<()> {
+    let label = match segment {
+        PathSegment::Key(key) => String::from_utf8_lossy(key).into_owned(),
+        PathSegment::Index(_) => segment.to_string(),
+    };
+    let connector = if last { "└── " } else { "├── " };
+    let children = match expand(value, depth, options) {
+        Some(children) => children,
+        None => {
+            return writeln!(
+                w,
+                "{}{}{} = {}",
+                indent,
+                connector,
+                label,
+                describe_value(value, options)
+            )
+        }
+    };
+    writeln!(w, "{}{}{}", indent, connector, label)?;
+    let indent = format!("{}{}", indent, if last { "    " } else { "│   " });
+    print_children(&children, &indent, depth + 1, w, options)
+}
+
+fn print_children(
+    children: &[(PathSegment, &Value)],
+    indent: &str,
+    depth: usize,
+    w: &mut impl Write,
+    options: &PrintOptions,
+) -> std::io::Result<()> {
+    for (ind, (segment, child)) in children.iter().enumerate() {
+        let last = ind + 1 == children.len();
+        print_tree(child, segment, indent, last, depth, w, options)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bencoding::parse::try_parse_value;
+
+    const TORRENT: &[u8] =
+        b"d8:announce3:url4:infod5:filesld6:lengthi3e4:pathl1:a1:beee4:name4:root6:pieces2:\xff\xfeee";
+
+    fn print(options: PrintOptions) -> String {
+        let value = try_parse_value(TORRENT.iter().copied()).unwrap();
+        let mut res = Vec::new();
+        pretty_print_with_options(&value, &mut res, &options).unwrap();
+        String::from_utf8(res).unwrap()
+    }
+
+    #[test]
+    fn test_flat() {
+        assert_eq!(
+            print(PrintOptions::default()),
+            "['announce'] = 'url'
+['info']['files'][0]['length'] = 3
+['info']['files'][0]['path'][0] = 'a'
+['info']['files'][0]['path'][1] = 'b'
+['info']['name'] = 'root'
+['info']['pieces'] = 0xfffe
+"
+        );
+        assert_eq!(
+            print(PrintOptions {
+                max_depth: Some(2),
+                binary: BinaryRendering::Base64,
+                ..PrintOptions::default()
+            }),
+            "['announce'] = 'url'
+['info']['files'] = list of 1
+['info']['name'] = 'root'
+['info']['pieces'] = base64://4=
+"
+        );
+    }
+
+    #[test]
+    fn test_tree() {
+        assert_eq!(
+            print(PrintOptions {
+                layout: Layout::Tree,
+                binary: BinaryRendering::Lossy,
+                ..PrintOptions::default()
+            }),
+            "├── announce = 'url'
+└── info
+    ├── files
+    │   └── [0]
+    │       ├── length = 3
+    │       └── path
+    │           ├── [0] = 'a'
+    │           └── [1] = 'b'
+    ├── name = 'root'
+    └── pieces = '\u{fffd}\u{fffd}'
+"
+        );
+    }
+
+    #[test]
+    fn test_shorten() {
+        let options = PrintOptions {
+            max_string_length: Some(20),
+            ..PrintOptions::default()
+        };
+        let value = Value::String(b"0123456789abcdefghijklmnopqrstuvwxyz".to_vec());
+        assert_eq!(describe_value(&value, &options), "'012...30 chars...xyz'");
+        let value = Value::String(b"0123456789abcdefghij".to_vec());
+        assert_eq!(describe_value(&value, &options), "'0123456789abcdefghij'");
+
+        for max in 0..40 {
+            let options = PrintOptions {
+                max_string_length: Some(max),
+                ..PrintOptions::default()
+            };
+            let value = Value::String(vec![b'x'; 36]);
+            // Too small a limit leaves the string as it is.
+            let shown = describe_value(&value, &options).len() - 2;
+            assert!(
+                shown <= max || (max < 16 && shown == 36),
+                "{} for {}",
+                shown,
+                max
+            );
+        }
+    }
+}
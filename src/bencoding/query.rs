@@ -0,0 +1,403 @@
+//! Selects values by path, using the prefixes `print_torrent` prints.
+//!
+//! A query is a sequence of selectors:
+//!
+//! - `['key']` picks a dictionary entry. Inside the quotes `\'`, `\\` and
+//!   `\xNN` (any byte, for binary keys) are escapes.
+//! - `[3]` picks a list element.
+//! - `[*]` picks every element of a list or every value of a dictionary.
+//!
+//! For example `['info']['files'][*]['length']` selects the length of every
+//! file of a multi-file torrent. The empty query selects the root value.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use super::path::PathSegment;
+use super::value::Value;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selector {
+    Key(Vec<u8>),
+    Index(usize),
+    Wildcard,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query(Vec<Selector>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryErrorKind {
+    OpeningBracketExpected,
+    ClosingBracketExpected,
+    /// Neither a quoted key, an index nor `*` inside the brackets.
+    SelectorExpected,
+    UnterminatedKey,
+    InvalidEscape,
+    IndexOverflow,
+}
+
+impl Display for QueryErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let description = match self {
+            QueryErrorKind::OpeningBracketExpected => "expected '['",
+            QueryErrorKind::ClosingBracketExpected => "expected ']'",
+            QueryErrorKind::SelectorExpected => "expected a quoted key, an index or '*'",
+            QueryErrorKind::UnterminatedKey => "key is missing its closing quote",
+            QueryErrorKind::InvalidEscape => "invalid escape sequence",
+            QueryErrorKind::IndexOverflow => "index is too large",
+        };
+        f.write_str(description)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryError {
+    pub kind: QueryErrorKind,
+    pub offset: usize,
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at offset {}", self.kind, self.offset)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// A value selected by a query together with its concrete path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match<'v> {
+    pub path: Vec<PathSegment>,
+    pub value: &'v Value,
+}
+
+impl Query {
+    pub fn new(selectors: Vec<Selector>) -> Self {
+        Query(selectors)
+    }
+
+    pub fn selectors(&self) -> &[Selector] {
+        &self.0
+    }
+
+    /// All values matching the query, in document order.
+    pub fn select<'v>(&self, value: &'v Value) -> Vec<Match<'v>> {
+        let mut matches = Vec::new();
+        select(value, &self.0, &mut Vec::new(), &mut matches);
+        matches
+    }
+}
+
+fn select<'v>(
+    value: &'v Value,
+    selectors: &[Selector],
+    path: &mut Vec<PathSegment>,
+    matches: &mut Vec<Match<'v>>,
+) {
+    let (selector, rest) = match selectors.split_first() {
+        Some(split) => split,
+        None => {
+            matches.push(Match {
+                path: path.clone(),
+                value,
+            });
+            return;
+        }
+    };
+    let mut visit = |segment: PathSegment, child: &'v Value| {
+        path.push(segment);
+        select(child, rest, path, matches);
+        path.pop();
+    };
+    match (selector, value) {
+        (Selector::Key(key), Value::Dictionary(kv)) => {
+            if let Some(child) = kv.get(key) {
+                visit(PathSegment::Key(key.clone()), child);
+            }
+        }
+        (Selector::Index(ind), Value::List(values)) => {
+            if let Some(child) = values.get(*ind) {
+                visit(PathSegment::Index(*ind), child);
+            }
+        }
+        (Selector::Wildcard, Value::Dictionary(kv)) => {
+            for (key, child) in kv {
+                visit(PathSegment::Key(key.to_vec()), child);
+            }
+        }
+        (Selector::Wildcard, Value::List(values)) => {
+            for (ind, child) in values.iter().enumerate() {
+                visit(PathSegment::Index(ind), child);
+            }
+        }
+        _ => {}
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = QueryParser {
+            source: s.as_bytes(),
+            pos: 0,
+        };
+        let mut selectors = Vec::new();
+        while parser.pos < s.len() {
+            selectors.push(parser.selector()?);
+        }
+        Ok(Query(selectors))
+    }
+}
+
+struct QueryParser<'a> {
+    source: &'a [u8],
+    pos: usize,
+}
+
+impl QueryParser<'_> {
+    fn error(&self, kind: QueryErrorKind) -> QueryError {
+        QueryError {
+            kind,
+            offset: self.pos,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.source.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, expected: u8, kind: QueryErrorKind) -> Result<(), QueryError> {
+        if self.peek() != Some(expected) {
+            return Err(self.error(kind));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn selector(&mut self) -> Result<Selector, QueryError> {
+        self.expect(b'[', QueryErrorKind::OpeningBracketExpected)?;
+        let selector = match self.peek() {
+            Some(b'*') => {
+                self.pos += 1;
+                Selector::Wildcard
+            }
+            Some(b'\'') => Selector::Key(self.key()?),
+            Some(b'0'..=b'9') => Selector::Index(self.index()?),
+            _ => return Err(self.error(QueryErrorKind::SelectorExpected)),
+        };
+        self.expect(b']', QueryErrorKind::ClosingBracketExpected)?;
+        Ok(selector)
+    }
+
+    fn index(&mut self) -> Result<usize, QueryError> {
+        let start = self.pos;
+        let mut index: usize = 0;
+        while let Some(b @ b'0'..=b'9') = self.peek() {
+            index = index
+                .checked_mul(10)
+                .and_then(|index| index.checked_add((b - b'0') as usize))
+                .ok_or(QueryError {
+                    kind: QueryErrorKind::IndexOverflow,
+                    offset: start,
+                })?;
+            self.pos += 1;
+        }
+        Ok(index)
+    }
+
+    fn key(&mut self) -> Result<Vec<u8>, QueryError> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut key = Vec::new();
+        loop {
+            match self.peek() {
+                Some(b'\'') => {
+                    self.pos += 1;
+                    return Ok(key);
+                }
+                Some(b'\\') => {
+                    key.push(self.escape()?);
+                }
+                Some(b) => {
+                    key.push(b);
+                    self.pos += 1;
+                }
+                None => {
+                    return Err(QueryError {
+                        kind: QueryErrorKind::UnterminatedKey,
+                        offset: start,
+                    })
+                }
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<u8, QueryError> {
+        let start = self.pos;
+        let invalid = QueryError {
+            kind: QueryErrorKind::InvalidEscape,
+            offset: start,
+        };
+        let byte = match self.source.get(start + 1) {
+            Some(b @ (b'\'' | b'\\')) => {
+                self.pos += 2;
+                *b
+            }
+            Some(b'x') => {
+                let hex = self
+                    .source
+                    .get(start + 2..start + 4)
+                    .ok_or(invalid.clone())?;
+                let hex = std::str::from_utf8(hex).map_err(|_| invalid.clone())?;
+                let byte = u8::from_str_radix(hex, 16).map_err(|_| invalid.clone())?;
+                self.pos += 4;
+                byte
+            }
+            _ => return Err(invalid),
+        };
+        Ok(byte)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bencoding::path::format_path;
+    use crate::bencoding::utils::str_to_value;
+
+    fn query(s: &str) -> Query {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(query(""), Query::new(vec![]));
+        assert_eq!(
+            query(r"['info']['files'][12][*]['it\'s \\ \x00']"),
+            Query::new(vec![
+                Selector::Key(b"info".to_vec()),
+                Selector::Key(b"files".to_vec()),
+                Selector::Index(12),
+                Selector::Wildcard,
+                Selector::Key(b"it's \\ \x00".to_vec()),
+            ])
+        );
+        assert_eq!(
+            query("['*']"),
+            Query::new(vec![Selector::Key(b"*".to_vec())])
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |s: &str| s.parse::<Query>().unwrap_err();
+        assert_eq!(
+            error("info"),
+            QueryError {
+                kind: QueryErrorKind::OpeningBracketExpected,
+                offset: 0
+            }
+        );
+        assert_eq!(error("[info]").kind, QueryErrorKind::SelectorExpected);
+        assert_eq!(
+            error("['info'"),
+            QueryError {
+                kind: QueryErrorKind::ClosingBracketExpected,
+                offset: 7
+            }
+        );
+        assert_eq!(error("['info]").kind, QueryErrorKind::UnterminatedKey);
+        assert_eq!(error(r"['\n']").kind, QueryErrorKind::InvalidEscape);
+        assert_eq!(error(r"['\xg0']").kind, QueryErrorKind::InvalidEscape);
+        assert_eq!(
+            error("[99999999999999999999999]").kind,
+            QueryErrorKind::IndexOverflow
+        );
+    }
+
+    #[test]
+    fn test_select() {
+        let value = str_to_value(
+            "d4:infod5:filesld6:lengthi1e4:pathl1:aeed6:lengthi2e4:pathl1:bd1:xi0eeeee4:name1:nee",
+        )
+        .unwrap();
+        let select = |s: &str| {
+            query(s)
+                .select(&value)
+                .into_iter()
+                .map(|m| {
+                    format!(
+                        "{} = {}",
+                        format_path(&m.path),
+                        m.value.to_bytes().escape_ascii()
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(select("['info']['name']"), ["['info']['name'] = 1:n"]);
+        assert_eq!(
+            select("['info']['files'][*]['length']"),
+            [
+                "['info']['files'][0]['length'] = i1e",
+                "['info']['files'][1]['length'] = i2e"
+            ]
+        );
+        assert_eq!(
+            select("['info']['files'][1]['path'][*]"),
+            [
+                "['info']['files'][1]['path'][0] = 1:b",
+                "['info']['files'][1]['path'][1] = d1:xi0ee"
+            ]
+        );
+        assert_eq!(
+            select("[*][*][*][*][*]['x']"),
+            ["['info']['files'][1]['path'][1]['x'] = i0e"]
+        );
+        assert!(select("['info']['files'][2]").is_empty());
+        assert!(select("['info']['name'][*]").is_empty());
+        assert_eq!(select("").len(), 1);
+    }
+
+    #[test]
+    fn test_printed_paths_round_trip() {
+        let keys: [&[u8]; 5] = [
+            b"it's",
+            b"a\\b",
+            b"\xff\x00",
+            "\u{e9}t\u{e9}".as_bytes(),
+            b"['x']",
+        ];
+        let value = Value::Dictionary(
+            keys.iter()
+                .map(|key| (*key, Value::List(vec![Value::String(key.to_vec())])))
+                .collect(),
+        );
+        let printed = query("[*][0]")
+            .select(&value)
+            .into_iter()
+            .map(|m| format_path(&m.path))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            printed,
+            [
+                r"['[\'x\']'][0]",
+                r"['a\\b'][0]",
+                r"['it\'s'][0]",
+                "['\u{e9}t\u{e9}'][0]",
+                r"['\xff\x00'][0]",
+            ]
+        );
+        for path in &printed {
+            let matches = query(path).select(&value);
+            assert_eq!(matches.len(), 1, "{}", path);
+            assert_eq!(&format_path(&matches[0].path), path);
+            match (&matches[0].path[0], matches[0].value) {
+                (PathSegment::Key(key), Value::String(s)) => assert_eq!(key, s),
+                _ => panic!("unexpected match for {}", path),
+            }
+        }
+    }
+}
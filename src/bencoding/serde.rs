@@ -0,0 +1,244 @@
+//! Serde support for bencode (enabled with the `serde` feature).
+//!
+//! Values are deserialized from a parsed [`Value`] tree and serialized into
+//! one, which is then written with [`Value::encode`], so the output is always
+//! canonical bencode.
+//!
+//! ```ignore
+//! #[derive(Deserialize)]
+//! struct Info {
+//!     name: String,
+//!     #[serde(rename = "piece length")]
+//!     piece_length: u64,
+//!     #[serde(with = "torr::bencoding::serde::bytes")]
+//!     pieces: Vec<u8>,
+//! }
+//! ```
+
+use std::fmt::Display;
+
+use super::parse::{try_parse_value, ParseError};
+use super::value::Value;
+
+pub mod bytes;
+mod de;
+mod ser;
+
+pub use de::Deserializer;
+pub use ser::Serializer;
+
+#[derive(Debug)]
+pub enum Error {
+    Message(String),
+    Parse(ParseError),
+    Io(std::io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Message(msg) => write!(f, "{}", msg),
+            Error::Parse(e) => write!(f, "bencode parse error: {}", e),
+            Error::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ::serde::de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
+
+impl ::serde::ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::Parse(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+pub fn from_value<T: ::serde::de::DeserializeOwned>(value: Value) -> Result<T> {
+    T::deserialize(Deserializer::new(value))
+}
+
+pub fn from_bytes<T: ::serde::de::DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    from_value(try_parse_value(bytes.iter().copied())?)
+}
+
+pub fn to_value<T: ::serde::Serialize + ?Sized>(value: &T) -> Result<Value> {
+    match value.serialize(Serializer)? {
+        Some(value) => Ok(value),
+        None => Err(Error::Message(
+            "bencode cannot represent a missing value".to_owned(),
+        )),
+    }
+}
+
+pub fn to_writer<T: ::serde::Serialize + ?Sized>(
+    value: &T,
+    w: &mut impl std::io::Write,
+) -> Result<()> {
+    to_value(value)?.encode(w)?;
+    Ok(())
+}
+
+pub fn to_bytes<T: ::serde::Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    to_writer(value, &mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use ::serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct File {
+        length: u64,
+        path: Vec<String>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Info {
+        name: String,
+        #[serde(rename = "piece length")]
+        piece_length: u64,
+        #[serde(with = "super::bytes")]
+        pieces: Vec<u8>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        private: Option<bool>,
+        files: Vec<File>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct MetaInfo {
+        announce: String,
+        info: Info,
+        comment: Option<String>,
+    }
+
+    fn sample() -> MetaInfo {
+        MetaInfo {
+            announce: "http://tracker/announce".to_owned(),
+            info: Info {
+                name: "dir".to_owned(),
+                piece_length: 16384,
+                pieces: vec![0xff, 0x00, b':', 0x80],
+                private: Some(true),
+                files: vec![File {
+                    length: 3,
+                    path: vec!["a".to_owned(), "b.txt".to_owned()],
+                }],
+            },
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn test_serialize_struct() {
+        let bytes = to_bytes(&sample()).unwrap();
+        assert_eq!(
+            bytes,
+            b"d8:announce23:http://tracker/announce4:infod5:filesld6:lengthi3e4:pathl1:a5:b.txteee4:name3:dir12:piece lengthi16384e6:pieces4:\xff\x00:\x807:privatei1eee".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_deserialize_struct() {
+        let bytes = to_bytes(&sample()).unwrap();
+        assert_eq!(from_bytes::<MetaInfo>(&bytes).unwrap(), sample());
+    }
+
+    #[test]
+    fn test_missing_optional_field() {
+        let info: Info = from_bytes(b"d5:filesle4:name1:x12:piece lengthi1e6:pieces0:e").unwrap();
+        assert_eq!(info.private, None);
+    }
+
+    #[test]
+    fn test_type_mismatch() {
+        assert!(from_bytes::<File>(b"d6:length1:x4:pathlee").is_err());
+        assert!(from_bytes::<u8>(b"i256e").is_err());
+    }
+
+    #[test]
+    fn test_enums() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        enum Message {
+            Ping,
+            Piece(u32),
+            Request { index: u32, length: u32 },
+        }
+        for message in [
+            Message::Ping,
+            Message::Piece(7),
+            Message::Request {
+                index: 1,
+                length: 2,
+            },
+        ] {
+            let bytes = to_bytes(&message).unwrap();
+            assert_eq!(from_bytes::<Message>(&bytes).unwrap(), message);
+        }
+        assert_eq!(to_bytes(&Message::Ping).unwrap(), b"4:Ping");
+    }
+
+    #[test]
+    fn test_maps_and_values() {
+        let mut map = BTreeMap::new();
+        map.insert("spam".to_owned(), vec![1i64, 2]);
+        map.insert("cow".to_owned(), vec![]);
+        let bytes = to_bytes(&map).unwrap();
+        assert_eq!(bytes, b"d3:cowle4:spamli1ei2eee");
+
+        let value: Value = from_bytes(&bytes).unwrap();
+        assert_eq!(to_value(&value).unwrap(), value);
+    }
+
+    #[test]
+    fn test_large_integers() {
+        assert_eq!(to_bytes(&u64::MAX).unwrap(), b"i18446744073709551615e");
+        assert_eq!(
+            from_bytes::<u64>(b"i18446744073709551615e").unwrap(),
+            u64::MAX
+        );
+        assert_eq!(
+            from_bytes::<i128>(b"i-18446744073709551616e").unwrap(),
+            -(1 << 64)
+        );
+        assert!(from_bytes::<i64>(b"i9223372036854775808e").is_err());
+
+        // The serde data model stops at 128 bits.
+        let value = Value::List(vec![Value::BigInteger(
+            "340282366920938463463374607431768211456".parse().unwrap(),
+        )]);
+        assert!(to_bytes(&value).is_err());
+        assert!(from_value::<Value>(value).is_err());
+    }
+
+    #[test]
+    fn test_unsupported() {
+        assert!(to_bytes(&1.5f64).is_err());
+        assert!(to_bytes(&Option::<i64>::None).is_err());
+        assert!(to_bytes(&vec![Some(1), None]).is_err());
+    }
+}
@@ -0,0 +1,391 @@
+use ::serde::ser::{self, Serialize};
+
+use super::{Error, Result};
+use crate::bencoding::dictionary::Dictionary;
+use crate::bencoding::integer::BigInteger;
+use crate::bencoding::value::Value;
+
+/// Serializes Rust values into a bencode [`Value`].
+///
+/// Bencode has no representation for `None` or `()`, so they serialize to
+/// `Ok(None)`. Such entries are skipped inside dictionaries and structs and
+/// rejected everywhere else.
+pub struct Serializer;
+
+fn unsupported<T>(what: &str) -> Result<T> {
+    Err(Error::Message(format!("bencode cannot represent {}", what)))
+}
+
+fn required(value: Option<Value>) -> Result<Value> {
+    match value {
+        Some(value) => Ok(value),
+        None => unsupported("a missing value inside a list"),
+    }
+}
+
+fn string(s: &str) -> Value {
+    Value::String(s.as_bytes().to_vec())
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeList;
+    type SerializeMap = SerializeDictionary;
+    type SerializeStruct = SerializeDictionary;
+    type SerializeStructVariant = SerializeDictionary;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
+        Ok(Some(Value::Integer(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok> {
+        Ok(Some(BigInteger::from(v).into()))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok> {
+        Ok(Some(BigInteger::from(v).into()))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok> {
+        Ok(Some(BigInteger::from(v).into()))
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<Self::Ok> {
+        unsupported("floating point numbers")
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<Self::Ok> {
+        unsupported("floating point numbers")
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok> {
+        Ok(Some(string(v)))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok> {
+        Ok(Some(Value::String(v.to_vec())))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok> {
+        Ok(None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok> {
+        Ok(None)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok> {
+        let value = match value.serialize(Serializer)? {
+            Some(value) => value,
+            None => return unsupported("a missing value inside a variant"),
+        };
+        Ok(wrap_variant(Some(variant), value))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Ok(SerializeList {
+            values: Vec::with_capacity(len.unwrap_or(0)),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Ok(SerializeList {
+            values: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Ok(SerializeDictionary {
+            kv: Dictionary::new(),
+            key: None,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Ok(SerializeDictionary {
+            kv: Dictionary::new(),
+            key: None,
+            variant: Some(variant),
+        })
+    }
+}
+
+fn wrap_variant(variant: Option<&'static str>, value: Value) -> Option<Value> {
+    match variant {
+        Some(variant) => Some(Value::Dictionary([(variant, value)].into_iter().collect())),
+        None => Some(value),
+    }
+}
+
+pub struct SerializeList {
+    values: Vec<Value>,
+    variant: Option<&'static str>,
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.values.push(required(value.serialize(Serializer)?)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        Ok(wrap_variant(self.variant, Value::List(self.values)))
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeList {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+pub struct SerializeDictionary {
+    kv: Dictionary,
+    key: Option<Vec<u8>>,
+    variant: Option<&'static str>,
+}
+
+impl SerializeDictionary {
+    fn push(&mut self, key: impl Into<Vec<u8>>, value: &(impl Serialize + ?Sized)) -> Result<()> {
+        if let Some(value) = value.serialize(Serializer)? {
+            self.kv.insert(key, value);
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for SerializeDictionary {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        match key.serialize(Serializer)? {
+            Some(Value::String(key)) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => unsupported("dictionary keys that are not strings"),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        match self.key.take() {
+            Some(key) => self.push(key, value),
+            None => Err(Error::Message(
+                "serialize_value called before serialize_key".to_owned(),
+            )),
+        }
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        Ok(wrap_variant(self.variant, Value::Dictionary(self.kv)))
+    }
+}
+
+impl ser::SerializeStruct for SerializeDictionary {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeDictionary {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        use ser::{SerializeMap, SerializeSeq};
+
+        match self {
+            Value::String(bytes) => serializer.serialize_bytes(bytes),
+            Value::Integer(i) => serializer.serialize_i64(*i),
+            Value::BigInteger(i) => match (i.to_i128(), i.to_u128()) {
+                (Some(i), _) => serializer.serialize_i128(i),
+                (None, Some(u)) => serializer.serialize_u128(u),
+                (None, None) => Err(ser::Error::custom(format!("integer {} is too large", i))),
+            },
+            Value::List(values) => {
+                let mut seq = serializer.serialize_seq(Some(values.len()))?;
+                for v in values {
+                    seq.serialize_element(v)?;
+                }
+                seq.end()
+            }
+            Value::Dictionary(kv) => {
+                let mut map = serializer.serialize_map(Some(kv.len()))?;
+                for (k, v) in kv {
+                    map.serialize_entry(&Key(k), v)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+/// Serializes a dictionary key as a byte string rather than a list of bytes.
+struct Key<'a>(&'a [u8]);
+
+impl Serialize for Key<'_> {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
@@ -0,0 +1,382 @@
+use super::borrowed::BorrowedValue;
+use super::parse::{IParseResult, ParseError, ParseErrorKind, ParseOptions};
+use super::value::Value;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecoderOptions {
+    /// Maximum encoded length of a single top-level value in bytes, and of
+    /// the input buffered at any time.
+    pub max_length: usize,
+    pub parse: ParseOptions,
+}
+
+impl Default for DecoderOptions {
+    fn default() -> Self {
+        DecoderOptions {
+            max_length: 16 * 1024 * 1024,
+            parse: ParseOptions::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decoded {
+    /// A complete value, `consumed` being the length of its encoding.
+    Value { value: Value, consumed: usize },
+    /// The buffered input does not contain a complete value yet.
+    NeedMoreData,
+}
+
+/// What the scanner expects at the current position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Value,
+    Integer,
+    StringLength(u64),
+    String(u64),
+}
+
+/// Push-based decoder for bencoded values arriving in chunks, e.g. from a
+/// socket.
+///
+/// Input is buffered and scanned incrementally, so every byte is looked at
+/// once no matter how the input is split. Once a complete value is buffered
+/// it is parsed and removed from the buffer; further values may follow it.
+/// Offsets in errors are relative to the start of the current value. After
+/// an error the stream cannot be resynchronized and the decoder should be
+/// discarded.
+pub struct Decoder {
+    options: DecoderOptions,
+    buffer: Vec<u8>,
+    scanned: usize,
+    depth: usize,
+    token: Token,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Decoder::new(DecoderOptions::default())
+    }
+}
+
+impl Decoder {
+    pub fn new(options: DecoderOptions) -> Self {
+        Decoder {
+            options,
+            buffer: Vec::new(),
+            scanned: 0,
+            depth: 0,
+            token: Token::Value,
+        }
+    }
+
+    /// Buffers `bytes`. Fails without buffering anything if that would hold
+    /// more than `max_length` bytes; complete values have to be taken out
+    /// with [`decode`](Decoder::decode) before more input fits.
+    pub fn push(&mut self, bytes: &[u8]) -> IParseResult<()> {
+        let space = self.options.max_length.saturating_sub(self.buffer.len());
+        if bytes.len() > space {
+            return Err(ParseError::new(
+                ParseErrorKind::MaxLengthExceeded,
+                self.buffer.len() + space,
+                Some(bytes[space]),
+            ));
+        }
+        self.buffer.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Number of buffered bytes not yet returned as part of a value.
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Pushes `bytes` and tries to decode a value.
+    pub fn feed(&mut self, bytes: &[u8]) -> IParseResult<Decoded> {
+        self.push(bytes)?;
+        self.decode()
+    }
+
+    /// Returns the next complete value from the buffered input.
+    pub fn decode(&mut self) -> IParseResult<Decoded> {
+        let end = match self.scan() {
+            Ok(Some(end)) => end,
+            Ok(None) => return Ok(Decoded::NeedMoreData),
+            Err(e) => return Err(self.locate(e)),
+        };
+        let value =
+            BorrowedValue::parse_with_options(&self.buffer[..end], self.options.parse.clone())?
+                .to_value();
+        self.buffer.drain(..end);
+        self.scanned = 0;
+        Ok(Decoded::Value {
+            value,
+            consumed: end,
+        })
+    }
+
+    /// Signals the end of input: returns the buffered value, or the error
+    /// describing why the buffered input is incomplete.
+    pub fn finish(&mut self) -> IParseResult<Value> {
+        match self.decode()? {
+            Decoded::Value { value, .. } => Ok(value),
+            Decoded::NeedMoreData => {
+                match BorrowedValue::parse_with_options(&self.buffer, self.options.parse.clone()) {
+                    Err(e) => Err(e),
+                    Ok(_) => Err(ParseError::new(
+                        ParseErrorKind::UnexpectedEndOfInput,
+                        self.buffer.len(),
+                        None,
+                    )),
+                }
+            }
+        }
+    }
+
+    /// The scanner does not track the path of the value it is in, but the
+    /// parser reports the same syntax error with it.
+    fn locate(&self, e: ParseError) -> ParseError {
+        if matches!(
+            e.kind,
+            ParseErrorKind::MaxDepthExceeded | ParseErrorKind::MaxLengthExceeded
+        ) {
+            return e;
+        }
+        let prefix = &self.buffer[..self.buffer.len().min(e.offset + 1)];
+        match BorrowedValue::parse_with_options(prefix, self.options.parse.clone()) {
+            Err(located) if located.kind == e.kind && located.offset == e.offset => located,
+            _ => e,
+        }
+    }
+
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError::new(kind, self.scanned, self.buffer.get(self.scanned).copied())
+    }
+
+    /// Advances over the buffered bytes and returns the end of the first
+    /// top-level value once it is complete.
+    fn scan(&mut self) -> IParseResult<Option<usize>> {
+        while self.scanned < self.buffer.len() {
+            if self.scanned >= self.options.max_length {
+                return Err(self.error(ParseErrorKind::MaxLengthExceeded));
+            }
+            let b = self.buffer[self.scanned];
+            let mut value_ended = false;
+            match self.token {
+                Token::Value => match b {
+                    b'i' => self.token = Token::Integer,
+                    b'l' | b'd' => {
+                        if self.depth == self.options.parse.max_depth {
+                            return Err(self.error(ParseErrorKind::MaxDepthExceeded));
+                        }
+                        self.depth += 1;
+                    }
+                    b'e' if self.depth > 0 => {
+                        self.depth -= 1;
+                        value_ended = true;
+                    }
+                    b'0'..=b'9' => self.token = Token::StringLength((b - b'0') as u64),
+                    _ => return Err(self.error(ParseErrorKind::InvalidPrefix)),
+                },
+                Token::Integer => match b {
+                    b'e' => {
+                        self.token = Token::Value;
+                        value_ended = true;
+                    }
+                    b'-' | b'0'..=b'9' => {}
+                    _ => return Err(self.error(ParseErrorKind::IntegerSuffixExpected)),
+                },
+                Token::StringLength(len) => match b {
+                    b'0'..=b'9' => {
+                        let len = len
+                            .checked_mul(10)
+                            .and_then(|len| len.checked_add((b - b'0') as u64))
+                            .ok_or_else(|| self.error(ParseErrorKind::IntegerOverflow))?;
+                        if len > self.options.max_length as u64 {
+                            return Err(self.error(ParseErrorKind::MaxLengthExceeded));
+                        }
+                        self.token = Token::StringLength(len);
+                    }
+                    b':' if len == 0 => {
+                        self.token = Token::Value;
+                        value_ended = true;
+                    }
+                    b':' => self.token = Token::String(len),
+                    _ => return Err(self.error(ParseErrorKind::ColonExpected)),
+                },
+                Token::String(remaining) => {
+                    // The string is skipped in one go, so the check at the
+                    // top of the loop would not see it end past the limit.
+                    if self.scanned as u64 + remaining > self.options.max_length as u64 {
+                        return Err(self.error(ParseErrorKind::MaxLengthExceeded));
+                    }
+                    let available = (self.buffer.len() - self.scanned) as u64;
+                    let skipped = remaining.min(available);
+                    self.scanned += skipped as usize;
+                    if skipped < remaining {
+                        self.token = Token::String(remaining - skipped);
+                        continue;
+                    }
+                    self.token = Token::Value;
+                    if self.depth == 0 {
+                        return Ok(Some(self.scanned));
+                    }
+                    continue;
+                }
+            }
+            self.scanned += 1;
+            if value_ended && self.depth == 0 {
+                return Ok(Some(self.scanned));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bencoding::utils::str_to_value;
+
+    fn value(s: &str) -> Decoded {
+        Decoded::Value {
+            value: str_to_value(s).unwrap(),
+            consumed: s.len(),
+        }
+    }
+
+    #[test]
+    fn test_byte_by_byte() {
+        let source = "d4:infod6:lengthi12e4:pathl1:a4:spameee";
+        let mut decoder = Decoder::default();
+        for b in &source.as_bytes()[..source.len() - 1] {
+            assert_eq!(decoder.feed(&[*b]), Ok(Decoded::NeedMoreData));
+        }
+        assert_eq!(decoder.feed(b"e"), Ok(value(source)));
+        assert_eq!(decoder.buffered(), 0);
+    }
+
+    #[test]
+    fn test_several_values_in_one_chunk() {
+        let mut decoder = Decoder::default();
+        assert_eq!(decoder.feed(b"4:spami42el1:a"), Ok(value("4:spam")));
+        assert_eq!(decoder.decode(), Ok(value("i42e")));
+        assert_eq!(decoder.decode(), Ok(Decoded::NeedMoreData));
+        assert_eq!(decoder.buffered(), 4);
+        assert_eq!(decoder.feed(b"0:e"), Ok(value("l1:a0:e")));
+        assert_eq!(decoder.decode(), Ok(Decoded::NeedMoreData));
+    }
+
+    #[test]
+    fn test_split_string() {
+        let mut decoder = Decoder::default();
+        assert_eq!(decoder.feed(b"l10:01234"), Ok(Decoded::NeedMoreData));
+        assert_eq!(decoder.feed(b"56789"), Ok(Decoded::NeedMoreData));
+        assert_eq!(decoder.feed(b"ee"), Ok(value("l10:0123456789e")));
+    }
+
+    #[test]
+    fn test_limits() {
+        let options = DecoderOptions {
+            max_length: 10,
+            parse: ParseOptions {
+                max_depth: 2,
+                ..ParseOptions::default()
+            },
+        };
+
+        let mut decoder = Decoder::new(options.clone());
+        assert!(matches!(decoder.feed(b"llee"), Ok(Decoded::Value { .. })));
+        let err = decoder.feed(b"lll").unwrap_err();
+        assert_eq!(
+            (err.kind, err.offset),
+            (ParseErrorKind::MaxDepthExceeded, 2)
+        );
+
+        let mut decoder = Decoder::new(options.clone());
+        let err = decoder.feed(b"11:").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::MaxLengthExceeded);
+
+        // The length fits, but with its prefix the string does not.
+        let mut decoder = Decoder::new(options.clone());
+        assert!(matches!(
+            decoder.feed(b"8:12345678"),
+            Ok(Decoded::Value { .. })
+        ));
+        let err = decoder.feed(b"9:123456789").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::MaxLengthExceeded);
+        let mut decoder = Decoder::new(options.clone());
+        assert_eq!(decoder.feed(b"9:"), Ok(Decoded::NeedMoreData));
+        let err = decoder.feed(b"1").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::MaxLengthExceeded);
+
+        let mut decoder = Decoder::new(options);
+        assert_eq!(decoder.feed(b"li1ei2ei3e"), Ok(Decoded::NeedMoreData));
+        let err = decoder.feed(b"e").unwrap_err();
+        assert_eq!(
+            (err.kind, err.offset),
+            (ParseErrorKind::MaxLengthExceeded, 10)
+        );
+        assert_eq!(decoder.buffered(), 10);
+    }
+
+    #[test]
+    fn test_buffer_limit() {
+        let mut decoder = Decoder::new(DecoderOptions {
+            max_length: 10,
+            ..DecoderOptions::default()
+        });
+        // Complete values count too, until they are decoded.
+        assert_eq!(decoder.push(b"i1ei2ei3e"), Ok(()));
+        let err = decoder.push(b"i4e").unwrap_err();
+        assert_eq!(
+            (err.kind, err.offset, err.byte),
+            (ParseErrorKind::MaxLengthExceeded, 10, Some(b'4'))
+        );
+        assert_eq!(decoder.buffered(), 9);
+        assert_eq!(decoder.decode(), Ok(value("i1e")));
+        assert_eq!(decoder.feed(b"i4e"), Ok(value("i2e")));
+    }
+
+    #[test]
+    fn test_finish() {
+        let mut decoder = Decoder::default();
+        assert_eq!(decoder.feed(b"d4:infol1:a2:b"), Ok(Decoded::NeedMoreData));
+        let err = decoder.finish().unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnexpectedEndOfString);
+        assert_eq!(err.offset, 14);
+        assert_eq!(
+            crate::bencoding::path::format_path(&err.path),
+            "['info'][1]"
+        );
+
+        let mut decoder = Decoder::default();
+        assert_eq!(
+            decoder.finish().unwrap_err().kind,
+            ParseErrorKind::UnexpectedEndOfInput
+        );
+        decoder.push(b"i1e").unwrap();
+        assert_eq!(decoder.finish(), Ok(Value::Integer(1)));
+    }
+
+    #[test]
+    fn test_error_path() {
+        let err = Decoder::default().feed(b"d4:infod5:filesl1x").unwrap_err();
+        assert_eq!((err.kind, err.offset), (ParseErrorKind::ColonExpected, 17));
+        assert_eq!(
+            crate::bencoding::path::format_path(&err.path),
+            "['info']['files'][0]"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let kind = |s: &[u8]| Decoder::default().feed(s).unwrap_err().kind;
+        assert_eq!(kind(b"x"), ParseErrorKind::InvalidPrefix);
+        assert_eq!(kind(b"e"), ParseErrorKind::InvalidPrefix);
+        assert_eq!(kind(b"i1x"), ParseErrorKind::IntegerSuffixExpected);
+        assert_eq!(kind(b"4x"), ParseErrorKind::ColonExpected);
+        assert_eq!(kind(b"i-0e"), ParseErrorKind::NegativeZeroOccurred);
+        assert_eq!(kind(b"di1ei1ee"), ParseErrorKind::KeyExpectedToBeAString);
+    }
+}
@@ -1,14 +1,17 @@
 use std::borrow::Cow;
-use std::io::Read;
+use std::fmt::Display;
 
 use super::{
     json::write_as_json,
-    parse::{try_parse_value, IParseResult},
-    value::{Value, ValueType},
+    parse::{try_parse_value, IParseResult, ParseError},
+    print::{pretty_print, pretty_print_at, PrintOptions},
+    query::Query,
+    stream::{Decoded, Decoder, DecoderOptions},
+    value::Value,
 };
 
 pub fn str_to_value(s: &str) -> IParseResult<Value> {
-    try_parse_value(s.bytes().into_iter())
+    try_parse_value(s.bytes())
 }
 
 pub fn str_to_json(s: &str) -> String {
@@ -19,78 +22,195 @@ pub fn str_to_json(s: &str) -> String {
 pub fn value_to_json(value: &Value) -> String {
     let mut bytes: Vec<u8> = Vec::new();
 
-    write_as_json(&value, &mut bytes).unwrap();
+    write_as_json(value, &mut bytes).unwrap();
 
-    let res = String::from_utf8(bytes).unwrap();
-
-    res
+    String::from_utf8(bytes).unwrap()
 }
 
-pub fn str_keys_lossy(value: &Value) -> impl Iterator<Item = Cow<str>> {
-    value.keys().map(|k| k.to_lossy_str().unwrap())
+pub fn str_keys_lossy(value: &Value) -> impl Iterator<Item = Cow<'_, str>> {
+    value.keys().map(String::from_utf8_lossy)
+}
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
 }
-// Pretty Pring functions
-fn shorten(s: &str) -> String {
-    if s.len() <= 54 {
-        s.to_owned()
-    } else {
-        let chars = s.chars().collect::<Vec<_>>();
-
-        const START: usize = 20;
-        const END: usize = 20;
-        let start = &chars[0..START].iter().collect::<String>();
-        let end = &chars[(chars.len() - END)..].iter().collect::<String>();
-
-        format!("{}...{} chars...{}", start, chars.len() - START - END, end)
+
+/// Inverse of [`to_hex`]; accepts either case.
+pub fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
     }
+    s.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let digit = |b: u8| (b as char).to_digit(16);
+            Some((digit(pair[0])? << 4 | digit(pair[1])?) as u8)
+        })
+        .collect()
 }
 
-fn recursive_print(value: &Value, prefix: &str) {
-    match value.get_type() {
-        ValueType::String => {
-            let s = match value {
-                Value::String(bytes) => match String::from_utf8(bytes.clone()) {
-                    Ok(s) => s,
-                    Err(_) => format!(
-                        "0x{}",
-                        bytes
-                            .iter()
-                            .map(|b| format!("{:x}", *b))
-                            .collect::<String>()
-                    ),
-                },
-                _ => unreachable!(),
-            };
-            println!("{} = '{}'", prefix, shorten(&s));
-        }
-        ValueType::Integer => {
-            let int = match value {
-                Value::Integer(n) => *n,
-                _ => unreachable!(),
-            };
-            println!("{} = {}", prefix, int);
-        }
-        ValueType::List => {
-            for (ind, v) in value.values().enumerate() {
-                let new_prefix = format!("{}[{}]", prefix, ind);
-                recursive_print(v, &new_prefix);
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Standard base64 (RFC 4648) with padding.
+pub fn to_base64(bytes: &[u8]) -> String {
+    let mut res = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                res.push(BASE64_ALPHABET[(n >> (18 - 6 * i)) as usize & 0x3f] as char);
+            } else {
+                res.push('=');
             }
         }
-        ValueType::Dictionary => {
-            for (key, value) in value.entries() {
-                let new_prefix = format!("{}['{}']", prefix, key.to_lossy_str().unwrap());
-                recursive_print(value, &new_prefix);
-            }
+    }
+    res
+}
+
+/// Failure to read a bencoded value from a reader.
+#[derive(Debug)]
+pub enum DecodeError {
+    Io(std::io::Error),
+    Parse(ParseError),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::Io(e) => write!(f, "{}", e),
+            DecodeError::Parse(e) => write!(f, "{}", e),
         }
     }
 }
 
-pub fn print_metainfo(
-    r: &mut impl std::io::Read,
-) -> Result<(), crate::bencoding::parse::ParseError> {
-    let source = r.bytes().take_while(|x| x.is_ok()).map(|x| x.unwrap());
-    let value = try_parse_value(source)?;
+impl std::error::Error for DecodeError {}
+
+impl From<std::io::Error> for DecodeError {
+    fn from(e: std::io::Error) -> Self {
+        DecodeError::Io(e)
+    }
+}
+
+impl From<ParseError> for DecodeError {
+    fn from(e: ParseError) -> Self {
+        DecodeError::Parse(e)
+    }
+}
+
+/// The largest torrent file [`read_metainfo`] reads.
+pub const MAX_METAINFO_LENGTH: usize = 64 * 1024 * 1024;
 
-    recursive_print(&value, "");
+/// Decodes a whole torrent file from `r`, failing for files longer than
+/// [`MAX_METAINFO_LENGTH`].
+pub fn read_metainfo(r: &mut impl std::io::Read) -> Result<Value, DecodeError> {
+    let mut decoder = Decoder::new(DecoderOptions {
+        max_length: MAX_METAINFO_LENGTH,
+        ..DecoderOptions::default()
+    });
+    let mut chunk = vec![0; 64 * 1024];
+    loop {
+        // Never read past the limit, so that a value ending right at it is
+        // not rejected for bytes that follow it; a full buffer still reads
+        // one byte to tell the end of input from a value that is too long.
+        let length = (MAX_METAINFO_LENGTH - decoder.buffered())
+            .max(1)
+            .min(chunk.len());
+        let read = match r.read(&mut chunk[..length]) {
+            Ok(0) => return Ok(decoder.finish()?),
+            Ok(read) => read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        if let Decoded::Value { value, .. } = decoder.feed(&chunk[..read])? {
+            return Ok(value);
+        }
+    }
+}
+
+pub fn print_metainfo(r: &mut impl std::io::Read) -> Result<(), DecodeError> {
+    let value = read_metainfo(r)?;
+    pretty_print(&value, &mut std::io::stdout().lock())?;
     Ok(())
 }
+
+/// Prints every value matching `query`, each under its own path. Returns
+/// the number of matches.
+pub fn print_query(
+    value: &Value,
+    query: &Query,
+    w: &mut impl std::io::Write,
+    options: &PrintOptions,
+) -> std::io::Result<usize> {
+    let matches = query.select(value);
+    for m in &matches {
+        pretty_print_at(m.value, &m.path, w, options)?;
+    }
+    Ok(matches.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Error, ErrorKind, Read};
+
+    use super::*;
+    use crate::bencoding::parse::ParseErrorKind;
+
+    /// Returns `errors` in turn, then the data.
+    struct Flaky<'a> {
+        errors: Vec<ErrorKind>,
+        data: &'a [u8],
+    }
+
+    impl Read for Flaky<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.errors.pop() {
+                Some(kind) => Err(Error::new(kind, "flaky")),
+                None => self.data.read(buf),
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_metainfo_errors() {
+        let mut interrupted = Flaky {
+            errors: vec![ErrorKind::Interrupted, ErrorKind::Interrupted],
+            data: b"d1:ai1ee",
+        };
+        assert_eq!(
+            read_metainfo(&mut interrupted).unwrap(),
+            str_to_value("d1:ai1ee").unwrap()
+        );
+
+        let mut failing = Flaky {
+            errors: vec![ErrorKind::BrokenPipe],
+            data: b"d1:ai1ee",
+        };
+        assert!(matches!(
+            read_metainfo(&mut failing),
+            Err(DecodeError::Io(e)) if e.kind() == ErrorKind::BrokenPipe
+        ));
+        assert!(matches!(
+            read_metainfo(&mut &b"d1:a"[..]),
+            Err(DecodeError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_read_metainfo_limit() {
+        let string = |length: usize| {
+            std::io::Cursor::new(format!("{}:", length)).chain(std::io::repeat(b'a'))
+        };
+        // A value ending right at the limit, with more input after it.
+        assert!(matches!(
+            read_metainfo(&mut string(MAX_METAINFO_LENGTH - 9)),
+            Ok(Value::String(s)) if s.len() == MAX_METAINFO_LENGTH - 9
+        ));
+        assert!(matches!(
+            read_metainfo(&mut string(MAX_METAINFO_LENGTH - 8)),
+            Err(DecodeError::Parse(e)) if e.kind == ParseErrorKind::MaxLengthExceeded
+        ));
+    }
+}
@@ -1,4 +1,8 @@
 use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+
+use super::dictionary::Dictionary;
+use super::integer::{BigInteger, IntegerError};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ValueType {
@@ -28,35 +32,54 @@ Keys must be strings and appear in sorted order (sorted as raw strings, not alph
 Note that in the context of bencoding strings including dictionary keys are arbitrary byte sequences (uint8_t[]).
 BEP authors are encouraged to use ASCII-compatible strings for dictionary keys and UTF-8 for human-readable data. Implementations must not rely on this.
 */
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone)]
 pub enum Value {
     String(Vec<u8>),
     Integer(i64),
+    /// An integer outside the range of `i64`, see [`BigInteger`]. Integers
+    /// that fit are normally [`Value::Integer`]; build this variant with
+    /// `Value::from(BigInteger)`, which narrows. One built directly with an
+    /// integer that fits still equals the `Value::Integer`.
+    BigInteger(BigInteger),
     List(Vec<Value>),
-    Dictionary(Vec<(Value, Value)>),
+    Dictionary(Dictionary),
+}
+
+/// Integers compare by value, whichever variant holds them.
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Integer(a), Value::Integer(b)) => a == b,
+            (Value::BigInteger(a), Value::BigInteger(b)) => a == b,
+            (Value::Integer(a), Value::BigInteger(b))
+            | (Value::BigInteger(b), Value::Integer(a)) => b.to_i64() == Some(*a),
+            (Value::List(a), Value::List(b)) => a == b,
+            (Value::Dictionary(a), Value::Dictionary(b)) => a == b,
+            _ => false,
+        }
+    }
 }
 
+impl Eq for Value {}
+
 impl Value {
     pub fn get<'v>(&'v self, index: &Value) -> Option<&'v Value> {
         match index {
-            Value::String(_) => match self {
-                Value::Dictionary(kv) => {
-                    let res = kv
-                        .iter()
-                        .find_map(|(k, v)| if k == index { Some(v) } else { None });
-                    res
-                }
+            Value::String(key) => match self {
+                Value::Dictionary(kv) => kv.get(key),
                 _ => None,
             },
             Value::Integer(ind) => match self {
                 Value::List(vs) => vs.get(*ind as usize),
                 _ => None,
             },
+            Value::BigInteger(_) => None,
             Value::List(_) => None,
             Value::Dictionary(_) => None,
         }
     }
-    pub fn to_lossy_str(&self) -> Option<Cow<str>> {
+    pub fn to_lossy_str(&self) -> Option<Cow<'_, str>> {
         match self {
             Value::String(bytes) => Some(String::from_utf8_lossy(bytes)),
             _ => None,
@@ -74,7 +97,7 @@ impl Value {
     pub fn get_type(&self) -> ValueType {
         match self {
             Value::String(_) => ValueType::String,
-            Value::Integer(_) => ValueType::Integer,
+            Value::Integer(_) | Value::BigInteger(_) => ValueType::Integer,
             Value::List(_) => ValueType::List,
             Value::Dictionary(_) => ValueType::Dictionary,
         }
@@ -83,7 +106,7 @@ impl Value {
     pub fn len(&self) -> usize {
         match self {
             Value::String(s) => s.len(),
-            Value::Integer(_) => 1,
+            Value::Integer(_) | Value::BigInteger(_) => 1,
             Value::List(vs) => vs.len(),
             Value::Dictionary(kv) => kv.len(),
         }
@@ -93,45 +116,151 @@ impl Value {
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
+
+    /// The integer as `i64`, failing instead of wrapping when it is larger.
+    pub fn as_i64(&self) -> Result<i64, IntegerError> {
+        match self {
+            Value::Integer(i) => Ok(*i),
+            Value::BigInteger(i) => i.to_i64().ok_or(IntegerError::OutOfRange),
+            _ => Err(IntegerError::NotAnInteger(self.get_type())),
+        }
+    }
+
+    /// The integer as `u64`, failing when it is negative or too large.
+    pub fn as_u64(&self) -> Result<u64, IntegerError> {
+        match self {
+            Value::Integer(i) => u64::try_from(*i).map_err(|_| IntegerError::OutOfRange),
+            Value::BigInteger(i) => i.to_u64().ok_or(IntegerError::OutOfRange),
+            _ => Err(IntegerError::NotAnInteger(self.get_type())),
+        }
+    }
 }
+
+/// Conversion by reference, kept for existing callers.
+#[deprecated(note = "use `Value::from` or `Into<Value>`")]
 pub trait IntoValue {
+    #[allow(clippy::wrong_self_convention)]
     fn into_value(&self) -> Value;
 }
 
-impl IntoValue for i64 {
+#[allow(deprecated)]
+impl<T: Clone + Into<Value>> IntoValue for T {
     fn into_value(&self) -> Value {
-        Value::Integer(*self)
+        self.clone().into()
     }
 }
 
+#[allow(deprecated)]
 impl IntoValue for str {
     fn into_value(&self) -> Value {
-        let bytes = self.bytes().collect();
-        Value::String(bytes)
+        self.into()
     }
 }
 
-impl<'k, T: IntoValue, S: AsRef<str>> IntoValue for [(S, T)] {
+#[allow(deprecated)]
+impl<T: IntoValue, S: AsRef<str>> IntoValue for [(S, T)] {
     fn into_value(&self) -> Value {
-        let mut kv: Vec<(Value, Value)> = Vec::new();
+        Value::Dictionary(
+            self.iter()
+                .map(|(k, v)| (k.as_ref(), v.into_value()))
+                .collect(),
+        )
+    }
+}
+
+#[allow(deprecated)]
+impl<T: IntoValue> IntoValue for [T] {
+    fn into_value(&self) -> Value {
+        Value::List(self.iter().map(IntoValue::into_value).collect())
+    }
+}
+
+impl From<i32> for Value {
+    fn from(i: i32) -> Self {
+        Value::Integer(i.into())
+    }
+}
 
-        for (k, v) in self {
-            let key_str: &str = k.as_ref();
-            let value: Value = v.into_value();
-            kv.push((key_str.into_value(), value));
+impl From<u32> for Value {
+    fn from(i: u32) -> Self {
+        Value::Integer(i.into())
+    }
+}
+
+impl From<i64> for Value {
+    fn from(i: i64) -> Self {
+        Value::Integer(i)
+    }
+}
+
+/// Becomes a [`Value::BigInteger`] above `i64::MAX`.
+impl From<u64> for Value {
+    fn from(i: u64) -> Self {
+        match i64::try_from(i) {
+            Ok(i) => Value::Integer(i),
+            Err(_) => BigInteger::from(i).into(),
         }
+    }
+}
 
+impl From<usize> for Value {
+    fn from(i: usize) -> Self {
+        (i as u64).into()
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.as_bytes().to_vec())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s.into_bytes())
+    }
+}
+
+impl From<&[u8]> for Value {
+    fn from(bytes: &[u8]) -> Self {
+        Value::String(bytes.to_vec())
+    }
+}
+
+impl<const N: usize> From<&[u8; N]> for Value {
+    fn from(bytes: &[u8; N]) -> Self {
+        Value::String(bytes.to_vec())
+    }
+}
+
+/// A byte string, not a list of integers.
+impl From<Vec<u8>> for Value {
+    fn from(bytes: Vec<u8>) -> Self {
+        Value::String(bytes)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(values: Vec<T>) -> Self {
+        Value::List(values.into_iter().map(Into::into).collect())
+    }
+}
+
+impl From<Dictionary> for Value {
+    fn from(kv: Dictionary) -> Self {
         Value::Dictionary(kv)
     }
 }
 
-impl<T: IntoValue> IntoValue for [T] {
-    fn into_value(&self) -> Value {
-        let mut vals = Vec::new();
-        for x in self {
-            vals.push(x.into_value());
-        }
-        Value::List(vals)
+impl<K: Into<Vec<u8>>, V: Into<Value>> From<BTreeMap<K, V>> for Value {
+    fn from(map: BTreeMap<K, V>) -> Self {
+        Value::Dictionary(map.into_iter().map(|(k, v)| (k, v.into())).collect())
+    }
+}
+
+impl<K: Into<Vec<u8>>, V: Into<Value>, S> From<HashMap<K, V, S>> for Value {
+    fn from(map: HashMap<K, V, S>) -> Self {
+        Value::Dictionary(map.into_iter().map(|(k, v)| (k, v.into())).collect())
     }
 }
 
@@ -141,16 +270,17 @@ pub struct Keys<'v> {
 }
 
 impl<'v> Iterator for Keys<'v> {
-    type Item = &'v Value;
+    type Item = &'v [u8];
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.value {
             Value::String(_) => None,
             Value::Integer(_) => None,
+            Value::BigInteger(_) => None,
             Value::List(_) => None,
             Value::Dictionary(kv) => {
                 if self.current_index < kv.len() {
-                    let res = kv.get(self.current_index).map(|(k, _)| k);
+                    let res = kv.get_index(self.current_index).map(|(k, _)| k);
                     self.current_index += 1;
                     res
                 } else {
@@ -170,29 +300,27 @@ impl<'v> From<&'v Value> for Keys<'v> {
     }
 }
 
+/// Panics with an [`AccessError`](super::access::AccessError) message if
+/// the value is not a list or the index is out of bounds; use
+/// [`Value::get_path`] to handle that instead.
 impl std::ops::Index<usize> for Value {
     type Output = Value;
 
     fn index(&self, index: usize) -> &Self::Output {
-        match self {
-            Value::String(_) => unreachable!(),
-            Value::Integer(_) => unreachable!(),
-            Value::List(values) => &values[index],
-            Value::Dictionary(_) => unreachable!(),
-        }
+        self.get_path(&[index.into()])
+            .unwrap_or_else(|e| panic!("{}", e))
     }
 }
+
+/// Panics with an [`AccessError`](super::access::AccessError) message if
+/// the value is not a dictionary or the key is missing; use
+/// [`Value::get_path`] to handle that instead.
 impl std::ops::Index<&str> for Value {
     type Output = Value;
 
     fn index(&self, index: &str) -> &Self::Output {
-        match self {
-            Value::Dictionary(_) => {
-                let value = index.into_value();
-                return self.get(&value).unwrap();
-            }
-            _ => unreachable!(),
-        }
+        self.get_path(&[index.into()])
+            .unwrap_or_else(|e| panic!("{}", e))
     }
 }
 
@@ -200,7 +328,7 @@ impl std::ops::Index<&Value> for Value {
     type Output = Value;
 
     fn index<'v>(&'v self, index: &Value) -> &'v Value {
-        return self.get(index).unwrap();
+        self.get(index).expect("no such key or index")
     }
 }
 
@@ -222,7 +350,7 @@ impl<'v> Iterator for Values<'v> {
                     None
                 }
             }
-            int @ Value::Integer(_) => {
+            int @ (Value::Integer(_) | Value::BigInteger(_)) => {
                 if self.current_index == 0 {
                     self.current_index += 1;
                     Some(int)
@@ -241,9 +369,9 @@ impl<'v> Iterator for Values<'v> {
             }
             Value::Dictionary(kv) => {
                 if self.current_index < kv.len() {
-                    let res = &kv[self.current_index].1;
+                    let res = kv.get_index(self.current_index).map(|(_, v)| v);
                     self.current_index += 1;
-                    Some(res)
+                    res
                 } else {
                     None
                 }
@@ -267,18 +395,19 @@ pub struct KeyValues<'v> {
 }
 
 impl<'v> Iterator for KeyValues<'v> {
-    type Item = (&'v Value, &'v Value);
+    type Item = (&'v [u8], &'v Value);
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.value {
             Value::String(_) => None,
             Value::Integer(_) => None,
+            Value::BigInteger(_) => None,
             Value::List(_) => None,
             Value::Dictionary(kv) => {
                 if self.current_index < kv.len() {
-                    let (k, v) = &kv[self.current_index];
+                    let res = kv.get_index(self.current_index);
                     self.current_index += 1;
-                    Some((k, v))
+                    res
                 } else {
                     None
                 }
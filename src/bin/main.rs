@@ -1 +1,228 @@
-fn main() {}
+use std::io::Write;
+use std::path::Path;
+use std::process::exit;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use torr::bencoding::diff::diff;
+use torr::bencoding::parse::find_span;
+use torr::bencoding::path::PathSegment;
+use torr::bencoding::utils::to_hex;
+use torr::bencoding::utils::{read_metainfo, DecodeError};
+use torr::bencoding::value::Value;
+use torr::metainfo::create::{create, CreateOptions, Version};
+use torr::metainfo::read::from_bytes;
+use torr::metainfo::verify::verify;
+
+const USAGE: &str = "Usage:
+    torr diff <old.torrent> <new.torrent>
+    torr create [options] <path>
+    torr verify [--threads <n>] <file.torrent> <dir>
+
+Options for create:
+    -o, --output <file>        where to write the torrent (default <name>.torrent)
+    --version <v1|v2|hybrid>   metadata to include (default hybrid)
+    --piece-length <bytes>     power of two of at least 16384 (default by size)
+    --tracker <url>[,<url>...] adds a tier of trackers, may be repeated
+    --name <name>              name of the torrent (default the file name)
+    --comment <text>
+    --private
+    --threads <n>              hashing threads (default all cores)
+
+verify expects a single-file torrent at <dir>/<name> and the files of a
+multi-file torrent below <dir>/<name>/.";
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["diff", old, new] => diff_command(old, new),
+        ["create", ref rest @ ..] => create_command(rest),
+        ["verify", ref rest @ ..] => verify_command(rest),
+        _ => usage(),
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    exit(2);
+}
+
+/// Prints the differences between two bencoded files and whether the
+/// `info` dictionary, and with it the infohash, changed. Exits with 0 when
+/// the files are equal, 1 when they differ and 2 on errors, like diff(1).
+fn diff_command(old_path: &str, new_path: &str) {
+    let (old_bytes, old) = read_file(old_path);
+    let (new_bytes, new) = read_file(new_path);
+    let differences = diff(&old, &new);
+    for difference in &differences {
+        println!("{}", difference);
+    }
+
+    let info: &[PathSegment] = &["info".into()];
+    let old_info = raw_info(old_path, &old_bytes);
+    let new_info = raw_info(new_path, &new_bytes);
+    if old_info.is_some() || new_info.is_some() {
+        if old_info == new_info {
+            println!("info unchanged, infohash is the same");
+        } else if differences.iter().any(|d| d.is_under(info)) {
+            println!("info changed, infohash differs");
+        } else {
+            println!("info encoded differently, infohash differs");
+        }
+    }
+
+    if !differences.is_empty() || old_info != new_info {
+        exit(1);
+    }
+}
+
+fn read_file(path: &str) -> (Vec<u8>, Value) {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            exit(2);
+        }
+    };
+    match read_metainfo(&mut bytes.as_slice()) {
+        Ok(value) => (bytes, value),
+        Err(DecodeError::Parse(e)) => {
+            eprintln!("{}: {}", path, e);
+            eprintln!("{}", e.excerpt(&bytes));
+            exit(2);
+        }
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            exit(2);
+        }
+    }
+}
+
+/// The bytes of the top-level `info` dictionary, which the infohash is
+/// computed from.
+fn raw_info<'a>(path: &str, bytes: &'a [u8]) -> Option<&'a [u8]> {
+    match find_span(bytes, &["info".into()]) {
+        Ok(span) => span.map(|span| &bytes[span]),
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            exit(2);
+        }
+    }
+}
+
+fn create_command(args: &[&str]) {
+    let mut path = None;
+    let mut output = None;
+    let mut options = CreateOptions {
+        created_by: Some(format!("torr/{}", env!("CARGO_PKG_VERSION"))),
+        creation_date: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|d| d.as_secs() as i64),
+        ..CreateOptions::default()
+    };
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        let mut value = || match args.next() {
+            Some(&value) => value,
+            None => {
+                eprintln!("{} needs a value", arg);
+                usage();
+            }
+        };
+        match arg {
+            "-o" | "--output" => output = Some(value().to_owned()),
+            "--version" => {
+                options.version = match value() {
+                    "v1" => Version::V1,
+                    "v2" => Version::V2,
+                    "hybrid" => Version::Hybrid,
+                    _ => usage(),
+                }
+            }
+            "--piece-length" => options.piece_length = Some(parse_number(arg, value())),
+            "--tracker" => options
+                .trackers
+                .push(value().split(',').map(str::to_owned).collect()),
+            "--name" => options.name = Some(value().to_owned()),
+            "--comment" => options.comment = Some(value().to_owned()),
+            "--private" => options.private = true,
+            "--threads" => options.threads = parse_number(arg, value()) as usize,
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => usage(),
+        }
+    }
+    let path = path.unwrap_or_else(|| usage());
+
+    let meta = match create(path, &options) {
+        Ok(meta) => meta,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    };
+    let output = output.unwrap_or_else(|| format!("{}.torrent", meta.info.name));
+    let written =
+        std::fs::File::create_new(&output).and_then(|mut file| file.write_all(&meta.to_bytes()));
+    if let Err(e) = written {
+        eprintln!("{}: {}", output, e);
+        exit(1);
+    }
+
+    println!(
+        "{}: {} bytes, piece length {}",
+        Path::new(&output).display(),
+        meta.info.total_length(),
+        meta.info.piece_length
+    );
+    if let Some(hash) = meta.info_hash_v1() {
+        println!("v1 infohash: {}", to_hex(&hash));
+    }
+    if let Some(hash) = meta.info_hash_v2() {
+        println!("v2 infohash: {}", to_hex(&hash));
+    }
+}
+
+/// Hashes the data of a torrent and prints which files are complete.
+/// Exits with 0 when everything is valid, 1 when not and 2 on errors.
+fn verify_command(args: &[&str]) {
+    let (threads, torrent, dir) = match args {
+        ["--threads", threads, torrent, dir] => (parse_number("--threads", threads), torrent, dir),
+        [torrent, dir] if !torrent.starts_with('-') => (0, torrent, dir),
+        _ => usage(),
+    };
+    let (bytes, _) = read_file(torrent);
+    let meta = from_bytes(&bytes).unwrap_or_else(|e| {
+        eprintln!("{}: {}", torrent, e);
+        exit(2);
+    });
+    let report = verify(&meta, dir, threads as usize).unwrap_or_else(|e| {
+        eprintln!("{}: {}", torrent, e);
+        exit(2);
+    });
+
+    for file in &report.files {
+        let status = if file.missing {
+            "missing".to_owned()
+        } else if file.is_complete() {
+            "ok".to_owned()
+        } else {
+            format!("{}/{}", file.valid_pieces, file.pieces)
+        };
+        println!("{:<9}{}", status, file.path.join("/"));
+    }
+    println!(
+        "{} of {} pieces valid",
+        report.pieces.count_ones(),
+        report.pieces.len()
+    );
+    if !report.is_complete() {
+        exit(1);
+    }
+}
+
+fn parse_number(name: &str, s: &str) -> u64 {
+    s.parse().unwrap_or_else(|_| {
+        eprintln!("{} expects a number, got {}", name, s);
+        exit(2);
+    })
+}
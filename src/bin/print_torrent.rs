@@ -1,23 +1,132 @@
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::process::exit;
-use torr::bencoding::utils::print_metainfo;
+use torr::bencoding::print::{BinaryRendering, Layout, PrintOptions};
+use torr::bencoding::query::Query;
+use torr::bencoding::utils::{print_query, read_metainfo, DecodeError};
 
-fn main() {
-    let path = get_file_path_from_args();
-    let mut file = open_file(path);
-    print_metainfo(&mut file).unwrap();
+const USAGE: &str = "Usage: print_torrent [options] <file>
+
+Options:
+    --query <path>             print only values matching <path>, e.g. ['info']['files'][*]['length']
+    --tree                     indent values under their parents instead of printing full paths
+    --max-depth <n>            summarize lists and dictionaries nested <n> levels deep
+    --max-string-length <n>    shorten longer strings, 0 to never shorten (default 54)
+    --binary <hex|base64|lossy>
+                               how to print strings that are not UTF-8 (default hex)";
+
+struct Args {
+    path: String,
+    query: Query,
+    options: PrintOptions,
 }
 
-fn get_file_path_from_args() -> String {
-    match std::env::args().skip(1).next() {
-        Some(path) => path,
-        None => {
-            println!("You should pass torrent file path as a single parameter");
+fn main() {
+    let Args {
+        path,
+        query,
+        options,
+    } = parse_args();
+    let mut file = open_file(&path);
+    let value = match read_metainfo(&mut file) {
+        Ok(value) => value,
+        Err(DecodeError::Parse(e)) => {
+            eprintln!("{}: {}", path, e);
+            // The error is in the first value, so its offset is also the
+            // offset in the file. Only the bytes the excerpt shows are read
+            // back.
+            let mut bytes = Vec::new();
+            let read = file.seek(SeekFrom::Start(0)).and_then(|_| {
+                (&mut file)
+                    .take(e.offset as u64 + 30)
+                    .read_to_end(&mut bytes)
+            });
+            if read.is_ok() {
+                eprintln!("{}", e.excerpt(&bytes));
+            }
             exit(1);
         }
+        Err(DecodeError::Io(e)) => {
+            eprintln!("{}: {}", path, e);
+            exit(e.raw_os_error().unwrap_or(1));
+        }
+    };
+    let mut stdout = std::io::stdout().lock();
+    let printed =
+        print_query(&value, &query, &mut stdout, &options).and_then(|n| stdout.flush().map(|_| n));
+    match printed {
+        Ok(0) => exit(1),
+        Ok(_) => {}
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    }
+}
+
+fn parse_args() -> Args {
+    let mut path = None;
+    let mut query = Query::new(Vec::new());
+    let mut options = PrintOptions::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| match args.next() {
+            Some(value) => value,
+            None => {
+                eprintln!("{} needs a value", name);
+                usage();
+            }
+        };
+        match arg.as_str() {
+            "--query" => {
+                let s = value(&arg);
+                query = s.parse().unwrap_or_else(|e| {
+                    eprintln!("--query {}: {}", s, e);
+                    exit(2);
+                });
+            }
+            "--tree" => options.layout = Layout::Tree,
+            "--max-depth" => options.max_depth = Some(parse_number(&arg, &value(&arg))),
+            "--max-string-length" => {
+                options.max_string_length = match parse_number(&arg, &value(&arg)) {
+                    0 => None,
+                    n => Some(n),
+                }
+            }
+            "--binary" => {
+                options.binary = match value(&arg).as_str() {
+                    "hex" => BinaryRendering::Hex,
+                    "base64" => BinaryRendering::Base64,
+                    "lossy" => BinaryRendering::Lossy,
+                    _ => usage(),
+                }
+            }
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => usage(),
+        }
+    }
+    match path {
+        Some(path) => Args {
+            path,
+            query,
+            options,
+        },
+        None => usage(),
     }
 }
 
-fn open_file(path: String) -> std::fs::File {
+fn parse_number(name: &str, s: &str) -> usize {
+    s.parse().unwrap_or_else(|_| {
+        eprintln!("{} expects a number, got {}", name, s);
+        exit(2);
+    })
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    exit(2);
+}
+
+fn open_file(path: &str) -> std::fs::File {
     match std::fs::File::open(path) {
         Ok(f) => f,
         Err(e) => exit(e.raw_os_error().unwrap_or(1)),
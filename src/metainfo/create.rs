@@ -0,0 +1,433 @@
+//! Authoring torrents from files on disk.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use super::hashing::{hash_pieces, plan_v1, plan_v2, thread_count, Source};
+use super::merkle::{self, MerkleTree};
+use super::{File, FileTree, Files, Info, InfoV1, InfoV2, MetaInfo, TreeFile};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Version {
+    V1,
+    V2,
+    /// Both v1 and v2 metadata, with files padded to piece boundaries so
+    /// that v1 and v2 peers can share the swarm.
+    #[default]
+    Hybrid,
+}
+
+impl Version {
+    fn has_v1(self) -> bool {
+        self != Version::V2
+    }
+
+    fn has_v2(self) -> bool {
+        self != Version::V1
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CreateOptions {
+    pub version: Version,
+    /// Picked from the total size by [`default_piece_length`] when `None`.
+    pub piece_length: Option<u64>,
+    /// Defaults to the name of the file or directory.
+    pub name: Option<String>,
+    /// Tiers of tracker URLs. The first URL becomes `announce`; when there
+    /// are several, all of them are written to `announce-list`.
+    pub trackers: Vec<Vec<String>>,
+    pub comment: Option<String>,
+    pub created_by: Option<String>,
+    pub creation_date: Option<i64>,
+    pub private: bool,
+    /// Number of hashing threads, all available cores when 0.
+    pub threads: usize,
+}
+
+#[derive(Debug)]
+pub enum CreateError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    /// A path that is not valid UTF-8 or has no file name.
+    InvalidPath(PathBuf),
+    /// The directory contains no files.
+    NoFiles,
+    /// Zero, or for v2 and hybrid torrents not a power of two of at least
+    /// 16 KiB.
+    InvalidPieceLength(u64),
+}
+
+impl Display for CreateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CreateError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            CreateError::InvalidPath(path) => {
+                write!(f, "{} is not a valid UTF-8 file name", path.display())
+            }
+            CreateError::NoFiles => write!(f, "there are no files to add"),
+            CreateError::InvalidPieceLength(length) => {
+                write!(f, "piece length {} is not allowed", length)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CreateError {}
+
+pub type CreateResult<T> = std::result::Result<T, CreateError>;
+
+fn io_error(path: &Path) -> impl FnOnce(std::io::Error) -> CreateError + '_ {
+    move |error| CreateError::Io {
+        path: path.to_owned(),
+        error,
+    }
+}
+
+/// Aims for about 1500 pieces, within 16 KiB to 16 MiB.
+pub fn default_piece_length(total_length: u64) -> u64 {
+    (total_length / 1500)
+        .next_power_of_two()
+        .clamp(16 * 1024, 16 * 1024 * 1024)
+}
+
+fn file_name(path: &Path) -> CreateResult<String> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(str::to_owned)
+        .ok_or_else(|| CreateError::InvalidPath(path.to_owned()))
+}
+
+/// Collects the files below `dir`. Symbolic links to files are followed,
+/// links to directories are skipped so that a link to a parent directory
+/// cannot make the walk loop.
+fn walk(dir: &Path, components: &mut Vec<String>, out: &mut Vec<Source>) -> CreateResult<()> {
+    for entry in std::fs::read_dir(dir).map_err(io_error(dir))? {
+        let path = entry.map_err(io_error(dir))?.path();
+        let is_link = std::fs::symlink_metadata(&path)
+            .map_err(io_error(&path))?
+            .is_symlink();
+        let metadata = std::fs::metadata(&path).map_err(io_error(&path))?;
+        if is_link && metadata.is_dir() {
+            continue;
+        }
+        components.push(file_name(&path)?);
+        if metadata.is_dir() {
+            walk(&path, components, out)?;
+        } else if metadata.is_file() {
+            out.push(Source {
+                path: Some(path.clone()),
+                components: components.clone(),
+                length: metadata.len(),
+            });
+        }
+        components.pop();
+    }
+    Ok(())
+}
+
+fn insert_tree_file(tree: &mut BTreeMap<String, FileTree>, components: &[String], file: TreeFile) {
+    match components {
+        [name] => {
+            tree.insert(name.clone(), FileTree::File(file));
+        }
+        [dir, rest @ ..] => {
+            let subtree = tree
+                .entry(dir.clone())
+                .or_insert_with(|| FileTree::Directory(BTreeMap::new()));
+            if let FileTree::Directory(entries) = subtree {
+                insert_tree_file(entries, rest, file);
+            }
+        }
+        [] => unreachable!("files have at least one path component"),
+    }
+}
+
+/// Builds a torrent for the file or directory at `path`, hashing its
+/// contents.
+pub fn create(path: impl AsRef<Path>, options: &CreateOptions) -> CreateResult<MetaInfo> {
+    let path = path.as_ref();
+    let metadata = std::fs::metadata(path).map_err(io_error(path))?;
+    let name = match &options.name {
+        Some(name) => name.clone(),
+        None => file_name(&std::fs::canonicalize(path).map_err(io_error(path))?)?,
+    };
+    let single = !metadata.is_dir();
+    let mut sources = Vec::new();
+    if single {
+        sources.push(Source {
+            path: Some(path.to_owned()),
+            components: vec![name.clone()],
+            length: metadata.len(),
+        });
+    } else {
+        walk(path, &mut Vec::new(), &mut sources)?;
+        sources.sort_by(|a, b| a.components.cmp(&b.components));
+    }
+    if sources.is_empty() {
+        return Err(CreateError::NoFiles);
+    }
+
+    let total_length = sources.iter().map(|s| s.length).sum();
+    let piece_length = options
+        .piece_length
+        .unwrap_or_else(|| default_piece_length(total_length));
+    let valid_v2 = piece_length.is_power_of_two() && piece_length >= merkle::BLOCK_SIZE as u64;
+    if piece_length == 0 || (options.version.has_v2() && !valid_v2) {
+        return Err(CreateError::InvalidPieceLength(piece_length));
+    }
+
+    let pieces = match options.version {
+        Version::V1 => plan_v1(&sources, piece_length),
+        Version::V2 => plan_v2(&sources, piece_length, false),
+        Version::Hybrid => plan_v2(&sources, piece_length, true),
+    };
+    let hashes = hash_pieces(&sources, &pieces, thread_count(options.threads))
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| CreateError::Io {
+            path: e.path,
+            error: e.error,
+        })?;
+
+    let v1 = options.version.has_v1().then(|| {
+        let files = if single {
+            Files::Single {
+                length: sources[0].length,
+            }
+        } else {
+            let mut files = Vec::new();
+            for (ind, source) in sources.iter().enumerate() {
+                files.push(File {
+                    length: source.length,
+                    path: source.components.clone(),
+                    attr: None,
+                });
+                let remainder = source.length % piece_length;
+                let is_last = ind + 1 == sources.len();
+                if options.version == Version::Hybrid && !is_last && remainder != 0 {
+                    let padding = piece_length - remainder;
+                    files.push(File {
+                        length: padding,
+                        path: vec![".pad".to_owned(), padding.to_string()],
+                        attr: Some("p".to_owned()),
+                    });
+                }
+            }
+            Files::Multiple(files)
+        };
+        InfoV1 {
+            pieces: hashes.iter().filter_map(|h| h.v1).collect(),
+            files,
+        }
+    });
+
+    let mut piece_layers = HashMap::new();
+    let v2 = options.version.has_v2().then(|| {
+        let mut layers = vec![Vec::new(); sources.len()];
+        for (piece, piece_hashes) in pieces.iter().zip(&hashes) {
+            if let (Some((source, _)), Some(hash)) = (piece.v2, piece_hashes.v2) {
+                layers[source].push(hash);
+            }
+        }
+        let mut tree = BTreeMap::new();
+        for (source, layer) in sources.iter().zip(layers) {
+            let pieces_root = match layer.len() {
+                0 => None,
+                1 => Some(layer[0]),
+                n => {
+                    let padding = merkle::zero_piece_root(piece_length);
+                    let root =
+                        MerkleTree::new(layer.clone(), n.next_power_of_two(), padding).root();
+                    piece_layers.insert(root, layer);
+                    Some(root)
+                }
+            };
+            let file = TreeFile {
+                length: source.length,
+                pieces_root,
+            };
+            insert_tree_file(&mut tree, &source.components, file);
+        }
+        InfoV2 {
+            file_tree: FileTree::Directory(tree),
+        }
+    });
+
+    let info = Info {
+        name,
+        piece_length,
+        private: options.private,
+        v1,
+        v2,
+    };
+    let trackers = options
+        .trackers
+        .iter()
+        .filter(|tier| !tier.is_empty())
+        .cloned()
+        .collect::<Vec<_>>();
+    let announce_list = if trackers.iter().map(Vec::len).sum::<usize>() > 1 {
+        trackers.clone()
+    } else {
+        Vec::new()
+    };
+    Ok(MetaInfo {
+        announce: trackers.first().map(|tier| tier[0].clone()),
+        announce_list,
+        raw_info: info.to_value().to_bytes(),
+        info,
+        piece_layers,
+        creation_date: options.creation_date,
+        comment: options.comment.clone(),
+        created_by: options.created_by.clone(),
+        encoding: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use sha1::{Digest, Sha1};
+
+    use super::*;
+    use crate::metainfo::read::from_bytes;
+    use crate::metainfo::test_util::{write_files, write_v2_torrent_files, TempDir, V2_TORRENT};
+    use crate::metainfo::Sha1Hash;
+
+    const PIECE: u64 = 16 * 1024;
+
+    fn fixture(name: &str, files: &[(&str, usize)]) -> (TempDir, Vec<Vec<u8>>) {
+        let dir = TempDir::new(&format!("create-{}", name));
+        let contents = write_files(dir.path(), files);
+        (dir, contents)
+    }
+
+    fn options(version: Version, piece_length: u64) -> CreateOptions {
+        CreateOptions {
+            version,
+            piece_length: Some(piece_length),
+            trackers: vec![vec!["http://a".to_owned()], vec!["http://b".to_owned()]],
+            threads: 3,
+            ..CreateOptions::default()
+        }
+    }
+
+    fn sha1_pieces(data: &[u8], piece_length: u64) -> Vec<Sha1Hash> {
+        data.chunks(piece_length as usize)
+            .map(|piece| Sha1::digest(piece).into())
+            .collect()
+    }
+
+    #[test]
+    fn test_v1() {
+        let (dir, contents) = fixture("v1", &[("a", 40_000), ("b", 3), ("c/d", 20_000)]);
+        let meta = create(dir.path(), &options(Version::V1, PIECE)).unwrap();
+
+        let v1 = meta.info.v1.as_ref().unwrap();
+        assert_eq!(v1.pieces, sha1_pieces(&contents.concat(), PIECE));
+        assert_eq!(v1.files.total_length(), 60_003);
+        assert_eq!(meta.info.v2, None);
+        assert_eq!(from_bytes(&meta.to_bytes()).unwrap(), meta);
+    }
+
+    #[test]
+    fn test_v2() {
+        let piece_length = 2 * PIECE;
+        let (dir, contents) = fixture("v2", &[("large", 2 * piece_length as usize + 5)]);
+        let meta = create(dir.join("large"), &options(Version::V2, piece_length)).unwrap();
+
+        let root = MerkleTree::from_data(&contents[0]).root();
+        let v2 = meta.info.v2.as_ref().unwrap();
+        let files = v2.file_tree.files();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].0, ["large"]);
+        assert_eq!(files[0].1.pieces_root, Some(root));
+        assert_eq!(meta.piece_layers[&root].len(), 3);
+        assert_eq!(meta.info.v1, None);
+        assert_eq!(from_bytes(&meta.to_bytes()).unwrap(), meta);
+    }
+
+    #[test]
+    fn test_v2_torrent() {
+        let expected = from_bytes(V2_TORRENT).unwrap();
+        let dir = TempDir::new("create-v2-torrent");
+        write_v2_torrent_files(dir.path());
+        let options = CreateOptions {
+            name: Some(expected.info.name.clone()),
+            trackers: Vec::new(),
+            ..options(Version::V2, expected.info.piece_length)
+        };
+        let meta = create(dir.path(), &options).unwrap();
+        assert_eq!(meta.raw_info, expected.raw_info);
+        assert_eq!(meta.piece_layers, expected.piece_layers);
+    }
+
+    #[test]
+    fn test_hybrid() {
+        let files = [
+            ("b/large", 3 * PIECE as usize + 5),
+            ("a.txt", 10),
+            ("empty", 0),
+        ];
+        let (dir, contents) = fixture("hybrid", &files);
+        let meta = create(dir.path(), &options(Version::Hybrid, PIECE)).unwrap();
+
+        assert_eq!(
+            meta.info.name,
+            dir.path().file_name().unwrap().to_str().unwrap()
+        );
+        assert_eq!(meta.announce.as_deref(), Some("http://a"));
+        assert_eq!(meta.announce_list.len(), 2);
+        meta.validate_hybrid().unwrap();
+        assert_eq!(from_bytes(&meta.to_bytes()).unwrap(), meta);
+
+        let v1 = meta.info.v1.as_ref().unwrap();
+        let paths = match &v1.files {
+            Files::Multiple(files) => files.iter().map(|f| f.path.join("/")).collect::<Vec<_>>(),
+            _ => panic!("expected multiple files"),
+        };
+        assert_eq!(
+            paths,
+            ["a.txt", ".pad/16374", "b/large", ".pad/16379", "empty"]
+        );
+        let padded = [
+            contents[1].clone(),
+            vec![0; 16374],
+            contents[0].clone(),
+            vec![0; 16379],
+        ]
+        .concat();
+        assert_eq!(v1.pieces, sha1_pieces(&padded, PIECE));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks() {
+        let (dir, contents) = fixture("symlinks", &[("c/d", 5)]);
+        std::os::unix::fs::symlink(dir.path(), dir.join("c/loop")).unwrap();
+        std::os::unix::fs::symlink(dir.join("c/d"), dir.join("link")).unwrap();
+        let meta = create(dir.path(), &options(Version::V1, PIECE)).unwrap();
+
+        let v1 = meta.info.v1.as_ref().unwrap();
+        let paths = match &v1.files {
+            Files::Multiple(files) => files.iter().map(|f| f.path.join("/")).collect::<Vec<_>>(),
+            _ => panic!("expected multiple files"),
+        };
+        assert_eq!(paths, ["c/d", "link"]);
+        assert_eq!(v1.pieces, sha1_pieces(&contents[0].repeat(2), PIECE));
+    }
+
+    #[test]
+    fn test_errors() {
+        let (dir, _) = fixture("errors", &[("a", 1)]);
+        let result = create(dir.path(), &options(Version::Hybrid, 1000));
+        assert!(matches!(result, Err(CreateError::InvalidPieceLength(1000))));
+        assert!(create(dir.path(), &options(Version::V1, 1000)).is_ok());
+        std::fs::remove_file(dir.join("a")).unwrap();
+        let result = create(dir.path(), &options(Version::V1, 1000));
+        assert!(matches!(result, Err(CreateError::NoFiles)));
+    }
+}
@@ -0,0 +1,272 @@
+//! Reading and hashing piece data, shared by torrent creation and
+//! verification.
+
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use sha1::{Digest, Sha1};
+
+use super::merkle::{self, MerkleTree, ZERO_HASH};
+use super::{Sha1Hash, Sha256Hash};
+
+/// A file of the torrent and where its data is on disk. Padding files have
+/// no path and read as zeros.
+pub(super) struct Source {
+    pub path: Option<PathBuf>,
+    pub components: Vec<String>,
+    pub length: u64,
+}
+
+/// Bytes of one file that go into a piece.
+pub(super) struct Segment {
+    pub source: usize,
+    pub offset: u64,
+    pub length: u64,
+}
+
+/// A unit of hashing work: the piece data is the segments followed by
+/// `padding` zero bytes.
+pub(super) struct Piece {
+    pub segments: Vec<Segment>,
+    pub padding: u64,
+    /// Whether the piece has a SHA-1 hash in `pieces`.
+    pub v1: bool,
+    /// For v2, the file the piece belongs to and the width of the merkle
+    /// subtree to build over its blocks.
+    pub v2: Option<(usize, usize)>,
+}
+
+#[derive(Default)]
+pub(super) struct PieceHashes {
+    pub v1: Option<Sha1Hash>,
+    pub v2: Option<Sha256Hash>,
+}
+
+/// A file that could not be read.
+pub(super) struct ReadError {
+    pub path: PathBuf,
+    pub error: std::io::Error,
+}
+
+/// Splits the concatenated files into pieces, for v1 torrents. `piece_length`
+/// must not be 0.
+pub(super) fn plan_v1(sources: &[Source], piece_length: u64) -> Vec<Piece> {
+    let mut pieces = Vec::new();
+    let mut current = Vec::new();
+    let mut filled = 0;
+    for (ind, source) in sources.iter().enumerate() {
+        let mut offset = 0;
+        while offset < source.length {
+            let length = (piece_length - filled).min(source.length - offset);
+            current.push(Segment {
+                source: ind,
+                offset,
+                length,
+            });
+            offset += length;
+            filled += length;
+            if filled == piece_length {
+                pieces.push(Piece {
+                    segments: std::mem::take(&mut current),
+                    padding: 0,
+                    v1: true,
+                    v2: None,
+                });
+                filled = 0;
+            }
+        }
+    }
+    if !current.is_empty() {
+        pieces.push(Piece {
+            segments: current,
+            padding: 0,
+            v1: true,
+            v2: None,
+        });
+    }
+    pieces
+}
+
+/// Splits every file into pieces of its own, for v2 and hybrid torrents.
+/// In hybrid torrents the last piece of every file but the last is padded
+/// to a full piece, matching the padding files.
+pub(super) fn plan_v2(sources: &[Source], piece_length: u64, hybrid: bool) -> Vec<Piece> {
+    let blocks_per_piece = merkle::blocks_per_piece(piece_length);
+    let mut pieces = Vec::new();
+    for (ind, source) in sources.iter().enumerate() {
+        let count = source.length.div_ceil(piece_length);
+        for piece in 0..count {
+            let offset = piece * piece_length;
+            let length = piece_length.min(source.length - offset);
+            let width = if count == 1 {
+                (length as usize)
+                    .div_ceil(merkle::BLOCK_SIZE)
+                    .next_power_of_two()
+            } else {
+                blocks_per_piece
+            };
+            let is_padded = hybrid && ind + 1 < sources.len();
+            pieces.push(Piece {
+                segments: vec![Segment {
+                    source: ind,
+                    offset,
+                    length,
+                }],
+                padding: if is_padded { piece_length - length } else { 0 },
+                v1: hybrid,
+                v2: Some((ind, width)),
+            });
+        }
+    }
+    pieces
+}
+
+/// Reads and hashes pieces a block at a time, reusing the last opened file.
+struct PieceHasher<'a> {
+    sources: &'a [Source],
+    open: Option<(usize, std::fs::File)>,
+    block: Vec<u8>,
+}
+
+impl PieceHasher<'_> {
+    /// Fails for files that are missing or shorter than the piece needs
+    /// before reading anything, so that lengths taken from a torrent never
+    /// decide how much is read or allocated.
+    fn check(&self, piece: &Piece) -> Result<(), ReadError> {
+        for segment in &piece.segments {
+            let path = match &self.sources[segment.source].path {
+                Some(path) => path,
+                None => continue,
+            };
+            let length = std::fs::metadata(path)
+                .map_err(|error| ReadError {
+                    path: path.clone(),
+                    error,
+                })?
+                .len();
+            if length < segment.offset.saturating_add(segment.length) {
+                return Err(ReadError {
+                    path: path.clone(),
+                    error: ErrorKind::UnexpectedEof.into(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn hash(&mut self, piece: &Piece) -> Result<PieceHashes, ReadError> {
+        self.check(piece)?;
+        let mut v1 = piece.v1.then(Sha1::new);
+        let mut leaves = Vec::new();
+        let mut update = |block: &[u8]| {
+            if let Some(hasher) = &mut v1 {
+                hasher.update(block);
+            }
+            if piece.v2.is_some() {
+                leaves.push(merkle::hash_block(block));
+            }
+        };
+        for segment in &piece.segments {
+            let path = &self.sources[segment.source].path;
+            let read_error = |error| ReadError {
+                path: path.clone().unwrap_or_default(),
+                error,
+            };
+            let mut file = match path {
+                Some(path) => Some(match &mut self.open {
+                    Some((ind, file)) if *ind == segment.source => file,
+                    open => {
+                        let file = std::fs::File::open(path).map_err(read_error)?;
+                        &mut open.insert((segment.source, file)).1
+                    }
+                }),
+                None => None,
+            };
+            if let Some(file) = &mut file {
+                file.seek(SeekFrom::Start(segment.offset))
+                    .map_err(read_error)?;
+            }
+            let mut remaining = segment.length;
+            while remaining > 0 {
+                let block = &mut self.block[..remaining.min(merkle::BLOCK_SIZE as u64) as usize];
+                match &mut file {
+                    Some(file) => file.read_exact(block).map_err(read_error)?,
+                    None => block.fill(0),
+                }
+                update(block);
+                remaining -= block.len() as u64;
+            }
+        }
+        let zeros = [0; merkle::BLOCK_SIZE];
+        let mut padding = piece.padding;
+        while padding > 0 {
+            let length = padding.min(zeros.len() as u64);
+            if let Some(hasher) = &mut v1 {
+                hasher.update(&zeros[..length as usize]);
+            }
+            padding -= length;
+        }
+        Ok(PieceHashes {
+            v1: v1.map(|hasher| hasher.finalize().into()),
+            v2: piece
+                .v2
+                .map(|(_, width)| MerkleTree::new(leaves, width, ZERO_HASH).root()),
+        })
+    }
+}
+
+/// Hashes all pieces on `threads` threads, returning the results in piece
+/// order. A piece whose data cannot be read fails on its own, so the other
+/// pieces are still hashed.
+pub(super) fn hash_pieces(
+    sources: &[Source],
+    pieces: &[Piece],
+    threads: usize,
+) -> Vec<Result<PieceHashes, ReadError>> {
+    let next = AtomicUsize::new(0);
+    let worker = || {
+        let mut hasher = PieceHasher {
+            sources,
+            open: None,
+            block: vec![0; merkle::BLOCK_SIZE],
+        };
+        let mut res = Vec::new();
+        loop {
+            let ind = next.fetch_add(1, Ordering::Relaxed);
+            let piece = match pieces.get(ind) {
+                Some(piece) => piece,
+                None => break,
+            };
+            res.push((ind, hasher.hash(piece)));
+        }
+        res
+    };
+
+    let results = std::thread::scope(|scope| {
+        let handles = (0..threads.clamp(1, pieces.len().max(1)))
+            .map(|_| scope.spawn(worker))
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("hashing thread panicked"))
+            .collect::<Vec<_>>()
+    });
+
+    let mut hashes = (0..pieces.len()).map(|_| None).collect::<Vec<_>>();
+    for (ind, result) in results.into_iter().flatten() {
+        hashes[ind] = Some(result);
+    }
+    hashes
+        .into_iter()
+        .map(|result| result.expect("every piece is hashed"))
+        .collect()
+}
+
+/// The number of threads to hash on, all available cores when 0.
+pub(super) fn thread_count(threads: usize) -> usize {
+    match threads {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    }
+}
@@ -0,0 +1,328 @@
+use std::fmt::Display;
+
+use super::{Files, MetaInfo};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HybridError {
+    /// The torrent lacks either the v1 or the v2 metadata.
+    NotHybrid,
+    FileCountMismatch {
+        v1: usize,
+        v2: usize,
+    },
+    PathMismatch {
+        index: usize,
+        v1: String,
+        v2: String,
+    },
+    LengthMismatch {
+        path: String,
+        v1: u64,
+        v2: u64,
+    },
+    /// A file whose length is not a multiple of the piece length is not
+    /// followed by a padding file.
+    MissingPadding(String),
+    InvalidPadding {
+        path: String,
+        expected: u64,
+        found: u64,
+    },
+    /// A padding file that does not follow a content file.
+    UnexpectedPadding(String),
+    PieceCountMismatch {
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl Display for HybridError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HybridError::NotHybrid => write!(f, "torrent is not a hybrid v1+v2 torrent"),
+            HybridError::FileCountMismatch { v1, v2 } => write!(
+                f,
+                "v1 metadata has {} files while v2 file tree has {}",
+                v1, v2
+            ),
+            HybridError::PathMismatch { index, v1, v2 } => write!(
+                f,
+                "file {} is '{}' in v1 metadata and '{}' in v2 file tree",
+                index, v1, v2
+            ),
+            HybridError::LengthMismatch { path, v1, v2 } => write!(
+                f,
+                "file '{}' has length {} in v1 metadata and {} in v2 file tree",
+                path, v1, v2
+            ),
+            HybridError::MissingPadding(path) => {
+                write!(f, "file '{}' is not followed by a padding file", path)
+            }
+            HybridError::InvalidPadding {
+                path,
+                expected,
+                found,
+            } => write!(
+                f,
+                "padding file '{}' has length {}, expected {}",
+                path, found, expected
+            ),
+            HybridError::UnexpectedPadding(path) => {
+                write!(f, "padding file '{}' does not follow a file", path)
+            }
+            HybridError::PieceCountMismatch { expected, found } => {
+                write!(f, "v1 metadata has {} pieces, expected {}", found, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for HybridError {}
+
+struct V1Entry {
+    path: Vec<String>,
+    length: u64,
+    padding: bool,
+}
+
+impl MetaInfo {
+    /// Checks that the v1 and v2 metadata of a hybrid torrent describe the
+    /// same files, that every file but the last is padded to a piece boundary
+    /// with a BEP 47 padding file, and that `pieces` covers the padded data.
+    pub fn validate_hybrid(&self) -> Result<(), HybridError> {
+        let (v1, v2) = match (&self.info.v1, &self.info.v2) {
+            (Some(v1), Some(v2)) => (v1, v2),
+            _ => return Err(HybridError::NotHybrid),
+        };
+        let piece_length = self.info.piece_length;
+
+        let entries = match &v1.files {
+            Files::Single { length } => vec![V1Entry {
+                path: vec![self.info.name.clone()],
+                length: *length,
+                padding: false,
+            }],
+            Files::Multiple(files) => files
+                .iter()
+                .map(|f| V1Entry {
+                    path: f.path.clone(),
+                    length: f.length,
+                    padding: f.is_padding(),
+                })
+                .collect(),
+        };
+
+        let mut content = Vec::new();
+        for (ind, entry) in entries.iter().enumerate() {
+            let path = entry.path.join("/");
+            if entry.padding {
+                let previous = match ind.checked_sub(1).map(|i| &entries[i]) {
+                    Some(previous) if !previous.padding => previous,
+                    _ => return Err(HybridError::UnexpectedPadding(path)),
+                };
+                let expected = match previous.length % piece_length {
+                    0 => 0,
+                    remainder => piece_length - remainder,
+                };
+                if expected == 0 || entry.length != expected {
+                    return Err(HybridError::InvalidPadding {
+                        path,
+                        expected,
+                        found: entry.length,
+                    });
+                }
+                continue;
+            }
+
+            let is_last = ind + 1 == entries.len();
+            let is_padded = entries.get(ind + 1).is_some_and(|next| next.padding);
+            if !is_last && !is_padded && entry.length % piece_length != 0 {
+                return Err(HybridError::MissingPadding(path));
+            }
+            content.push(entry);
+        }
+
+        let tree_files = v2.file_tree.files();
+        if content.len() != tree_files.len() {
+            return Err(HybridError::FileCountMismatch {
+                v1: content.len(),
+                v2: tree_files.len(),
+            });
+        }
+        for (index, (entry, (tree_path, tree_file))) in content.iter().zip(&tree_files).enumerate()
+        {
+            if &entry.path != tree_path {
+                return Err(HybridError::PathMismatch {
+                    index,
+                    v1: entry.path.join("/"),
+                    v2: tree_path.join("/"),
+                });
+            }
+            if entry.length != tree_file.length {
+                return Err(HybridError::LengthMismatch {
+                    path: entry.path.join("/"),
+                    v1: entry.length,
+                    v2: tree_file.length,
+                });
+            }
+        }
+
+        let expected = v1.files.total_length().div_ceil(piece_length) as usize;
+        if v1.pieces.len() != expected {
+            return Err(HybridError::PieceCountMismatch {
+                expected,
+                found: v1.pieces.len(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use super::*;
+    use crate::metainfo::{File, FileTree, Info, InfoV1, InfoV2, TreeFile};
+
+    const PIECE: u64 = 16384;
+
+    fn file(path: &[&str], length: u64, attr: Option<&str>) -> File {
+        File {
+            length,
+            path: path.iter().map(|s| s.to_string()).collect(),
+            attr: attr.map(str::to_owned),
+        }
+    }
+
+    fn tree(files: &[(&str, u64)]) -> FileTree {
+        FileTree::Directory(
+            files
+                .iter()
+                .map(|(name, length)| {
+                    (
+                        name.to_string(),
+                        FileTree::File(TreeFile {
+                            length: *length,
+                            pieces_root: Some([0; 32]),
+                        }),
+                    )
+                })
+                .collect::<BTreeMap<_, _>>(),
+        )
+    }
+
+    fn meta(files: Files, pieces: usize, file_tree: FileTree) -> MetaInfo {
+        MetaInfo {
+            announce: None,
+            announce_list: Vec::new(),
+            info: Info {
+                name: "root".to_owned(),
+                piece_length: PIECE,
+                private: false,
+                v1: Some(InfoV1 {
+                    pieces: vec![[0; 20]; pieces],
+                    files,
+                }),
+                v2: Some(InfoV2 { file_tree }),
+            },
+            piece_layers: HashMap::new(),
+            creation_date: None,
+            comment: None,
+            created_by: None,
+            encoding: None,
+            raw_info: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_valid_hybrid() {
+        let files = Files::Multiple(vec![
+            file(&["a"], 100, None),
+            file(&[".pad", "16284"], PIECE - 100, Some("p")),
+            file(&["b"], PIECE, None),
+            file(&["c"], 5, None),
+        ]);
+        let multiple = meta(files, 3, tree(&[("a", 100), ("b", PIECE), ("c", 5)]));
+        assert!(multiple.info.is_hybrid());
+        assert_eq!(multiple.validate_hybrid(), Ok(()));
+
+        let single = meta(Files::Single { length: 100 }, 1, tree(&[("root", 100)]));
+        assert_eq!(single.validate_hybrid(), Ok(()));
+    }
+
+    #[test]
+    fn test_invalid_hybrid() {
+        let padded = |length| {
+            vec![
+                file(&["a"], 100, None),
+                file(&[".pad", "x"], length, Some("p")),
+                file(&["b"], 5, None),
+            ]
+        };
+        let file_tree = tree(&[("a", 100), ("b", 5)]);
+
+        assert_eq!(
+            meta(Files::Multiple(padded(PIECE - 100)), 2, file_tree.clone()).validate_hybrid(),
+            Ok(())
+        );
+        assert_eq!(
+            meta(Files::Multiple(padded(10)), 2, file_tree.clone()).validate_hybrid(),
+            Err(HybridError::InvalidPadding {
+                path: ".pad/x".to_owned(),
+                expected: PIECE - 100,
+                found: 10,
+            })
+        );
+        assert_eq!(
+            meta(Files::Multiple(padded(PIECE - 100)), 3, file_tree.clone()).validate_hybrid(),
+            Err(HybridError::PieceCountMismatch {
+                expected: 2,
+                found: 3
+            })
+        );
+        assert_eq!(
+            meta(
+                Files::Multiple(vec![file(&["a"], 100, None), file(&["b"], 5, None)]),
+                1,
+                file_tree.clone()
+            )
+            .validate_hybrid(),
+            Err(HybridError::MissingPadding("a".to_owned()))
+        );
+        assert_eq!(
+            meta(
+                Files::Multiple(padded(PIECE - 100)),
+                2,
+                tree(&[("a", 100), ("c", 5)])
+            )
+            .validate_hybrid(),
+            Err(HybridError::PathMismatch {
+                index: 1,
+                v1: "b".to_owned(),
+                v2: "c".to_owned(),
+            })
+        );
+        assert_eq!(
+            meta(
+                Files::Multiple(padded(PIECE - 100)),
+                2,
+                tree(&[("a", 100), ("b", 6)])
+            )
+            .validate_hybrid(),
+            Err(HybridError::LengthMismatch {
+                path: "b".to_owned(),
+                v1: 5,
+                v2: 6,
+            })
+        );
+        assert_eq!(
+            meta(Files::Multiple(padded(PIECE - 100)), 2, tree(&[("a", 100)])).validate_hybrid(),
+            Err(HybridError::FileCountMismatch { v1: 2, v2: 1 })
+        );
+        let mut v1_only = meta(Files::Multiple(padded(PIECE - 100)), 2, file_tree);
+        v1_only.info.v2 = None;
+        assert_eq!(v1_only.validate_hybrid(), Err(HybridError::NotHybrid));
+    }
+}
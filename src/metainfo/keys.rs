@@ -1,3 +1,21 @@
 pub const ANNOUNCE_KEY: &'_ str = "announce";
+pub const ANNOUNCE_LIST_KEY: &'_ str = "announce-list";
 pub const INFO_KEY: &'_ str = "info";
 pub const PIECE_LAYERS: &'_ str = "piece layers";
+pub const CREATION_DATE_KEY: &'_ str = "creation date";
+pub const COMMENT_KEY: &'_ str = "comment";
+pub const CREATED_BY_KEY: &'_ str = "created by";
+pub const ENCODING_KEY: &'_ str = "encoding";
+
+pub const NAME_KEY: &'_ str = "name";
+pub const PIECE_LENGTH_KEY: &'_ str = "piece length";
+pub const PIECES_KEY: &'_ str = "pieces";
+pub const PRIVATE_KEY: &'_ str = "private";
+pub const LENGTH_KEY: &'_ str = "length";
+pub const FILES_KEY: &'_ str = "files";
+pub const PATH_KEY: &'_ str = "path";
+pub const ATTR_KEY: &'_ str = "attr";
+
+pub const META_VERSION_KEY: &'_ str = "meta version";
+pub const FILE_TREE_KEY: &'_ str = "file tree";
+pub const PIECES_ROOT_KEY: &'_ str = "pieces root";
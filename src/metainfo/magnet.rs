@@ -0,0 +1,395 @@
+//! Magnet links (BEP 9), with BEP 52 v2 infohashes and BEP 53 file
+//! selection.
+
+use std::fmt::Display;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use super::trackers::TrackerList;
+use super::{MetaInfo, Sha1Hash, Sha256Hash};
+use crate::bencoding::utils::{from_hex, to_hex};
+
+const PREFIX: &str = "magnet:?";
+const BTIH: &str = "urn:btih:";
+const BTMH: &str = "urn:btmh:";
+/// Multihash header of a SHA-256 digest: function code 0x12, length 32.
+const SHA256_MULTIHASH: &str = "1220";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Magnet {
+    /// From `xt=urn:btih:`, hex or base32 encoded.
+    pub info_hash_v1: Option<Sha1Hash>,
+    /// From `xt=urn:btmh:`, a SHA-256 multihash.
+    pub info_hash_v2: Option<Sha256Hash>,
+    /// `dn`, the name to show until the metadata is known.
+    pub display_name: Option<String>,
+    /// `tr`, tracker URLs.
+    pub trackers: Vec<String>,
+    /// `ws`, BEP 19 web seed URLs.
+    pub web_seeds: Vec<String>,
+    /// `x.pe`, peer addresses as `host:port`.
+    pub peers: Vec<String>,
+    /// `so`, the indices of the files to download, empty for all files.
+    pub select_only: Vec<RangeInclusive<usize>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MagnetError {
+    /// The URI does not start with `magnet:?`.
+    NotMagnet,
+    /// There is neither a `urn:btih` nor a `urn:btmh` exact topic.
+    MissingInfoHash,
+    InvalidInfoHash(String),
+    /// A `%` not followed by two hex digits, or an escape that does not
+    /// decode to UTF-8.
+    InvalidEscape(String),
+    InvalidFileSelection(String),
+}
+
+impl Display for MagnetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MagnetError::NotMagnet => write!(f, "not a magnet link"),
+            MagnetError::MissingInfoHash => write!(f, "magnet link has no infohash"),
+            MagnetError::InvalidInfoHash(xt) => write!(f, "invalid infohash '{}'", xt),
+            MagnetError::InvalidEscape(value) => write!(f, "invalid escape in '{}'", value),
+            MagnetError::InvalidFileSelection(so) => {
+                write!(f, "invalid file selection '{}'", so)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MagnetError {}
+
+impl Magnet {
+    /// Whether file `index` is to be downloaded.
+    pub fn is_selected(&self, index: usize) -> bool {
+        self.select_only.is_empty() || self.select_only.iter().any(|r| r.contains(&index))
+    }
+}
+
+/// Strips the `.1`, `.2`, ... suffix that distinguishes repeated
+/// parameters, e.g. `xt.1`.
+fn base_key(key: &str) -> &str {
+    match key.rsplit_once('.') {
+        Some((base, n)) if !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()) => base,
+        _ => key,
+    }
+}
+
+fn parse_hash<const N: usize>(xt: &str, encoded: &str) -> Result<[u8; N], MagnetError> {
+    let bytes = if encoded.len() == 2 * N {
+        from_hex(encoded)
+    } else {
+        from_base32(encoded)
+    };
+    bytes
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| MagnetError::InvalidInfoHash(xt.to_owned()))
+}
+
+fn parse_selection(so: &str) -> Result<Vec<RangeInclusive<usize>>, MagnetError> {
+    let invalid = || MagnetError::InvalidFileSelection(so.to_owned());
+    so.split(',')
+        .map(|item| {
+            let (start, end) = item.split_once('-').unwrap_or((item, item));
+            let start = start.parse().map_err(|_| invalid())?;
+            let end = end.parse().map_err(|_| invalid())?;
+            if start > end {
+                return Err(invalid());
+            }
+            Ok(start..=end)
+        })
+        .collect()
+}
+
+impl FromStr for Magnet {
+    type Err = MagnetError;
+
+    fn from_str(s: &str) -> Result<Magnet, MagnetError> {
+        let query = match s.get(..PREFIX.len()) {
+            Some(prefix) if prefix.eq_ignore_ascii_case(PREFIX) => &s[PREFIX.len()..],
+            _ => return Err(MagnetError::NotMagnet),
+        };
+        let mut magnet = Magnet::default();
+        // Only `xt` must be valid. Other parameters that cannot be decoded
+        // or parsed are dropped, as clients do with parameters they do not
+        // understand.
+        for parameter in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+            let key = base_key(key);
+            if key == "xt" {
+                let value = percent_decode(value)?;
+                if let Some(hash) = value.strip_prefix(BTIH) {
+                    magnet.info_hash_v1 = Some(parse_hash(&value, hash)?);
+                } else if let Some(multihash) = value.strip_prefix(BTMH) {
+                    let hash = multihash
+                        .strip_prefix(SHA256_MULTIHASH)
+                        .ok_or_else(|| MagnetError::InvalidInfoHash(value.clone()))?;
+                    magnet.info_hash_v2 = Some(parse_hash(&value, hash)?);
+                }
+                continue;
+            }
+            if !["dn", "tr", "ws", "x.pe", "so"].contains(&key) {
+                continue;
+            }
+            let value = match percent_decode(value) {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+            match key {
+                "dn" => magnet.display_name = Some(value),
+                "tr" => magnet.trackers.push(value),
+                "ws" => magnet.web_seeds.push(value),
+                "x.pe" => magnet.peers.push(value),
+                "so" => {
+                    if let Ok(selection) = parse_selection(&value) {
+                        magnet.select_only.extend(selection);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if magnet.info_hash_v1.is_none() && magnet.info_hash_v2.is_none() {
+            return Err(MagnetError::MissingInfoHash);
+        }
+        Ok(magnet)
+    }
+}
+
+impl Display for Magnet {
+    /// Writes infohashes in hex and percent-encodes everything else.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parameters = Vec::new();
+        if let Some(hash) = &self.info_hash_v1 {
+            parameters.push(format!("xt={}{}", BTIH, to_hex(hash)));
+        }
+        if let Some(hash) = &self.info_hash_v2 {
+            parameters.push(format!("xt={}{}{}", BTMH, SHA256_MULTIHASH, to_hex(hash)));
+        }
+        if let Some(name) = &self.display_name {
+            parameters.push(format!("dn={}", percent_encode(name)));
+        }
+        for (key, values) in [
+            ("tr", &self.trackers),
+            ("ws", &self.web_seeds),
+            ("x.pe", &self.peers),
+        ] {
+            for value in values {
+                parameters.push(format!("{}={}", key, percent_encode(value)));
+            }
+        }
+        if !self.select_only.is_empty() {
+            let ranges = self
+                .select_only
+                .iter()
+                .map(|range| {
+                    if range.start() == range.end() {
+                        range.start().to_string()
+                    } else {
+                        format!("{}-{}", range.start(), range.end())
+                    }
+                })
+                .collect::<Vec<_>>();
+            parameters.push(format!("so={}", ranges.join(",")));
+        }
+        write!(f, "{}{}", PREFIX, parameters.join("&"))
+    }
+}
+
+impl MetaInfo {
+    /// A magnet link with the infohashes, the name and the trackers of the
+    /// torrent.
+    pub fn to_magnet(&self) -> Magnet {
+        let trackers = TrackerList::from_meta(self).tiers().concat();
+        Magnet {
+            info_hash_v1: self.info_hash_v1(),
+            info_hash_v2: self.info_hash_v2(),
+            display_name: Some(self.info.name.clone()),
+            trackers,
+            ..Magnet::default()
+        }
+    }
+}
+
+/// Decodes `%XX` escapes, and `+` as a space as in form data.
+fn percent_decode(s: &str) -> Result<String, MagnetError> {
+    let invalid = || MagnetError::InvalidEscape(s.to_owned());
+    let mut res = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'%' => {
+                let hex = [
+                    bytes.next().ok_or_else(invalid)?,
+                    bytes.next().ok_or_else(invalid)?,
+                ];
+                let decoded = std::str::from_utf8(&hex)
+                    .ok()
+                    .and_then(from_hex)
+                    .ok_or_else(invalid)?;
+                res.extend(decoded);
+            }
+            b'+' => res.push(b' '),
+            b => res.push(b),
+        }
+    }
+    String::from_utf8(res).map_err(|_| invalid())
+}
+
+/// Escapes everything but RFC 3986 unreserved characters.
+fn percent_encode(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            res.push(b as char);
+        } else {
+            res.push_str(&format!("%{:02X}", b));
+        }
+    }
+    res
+}
+
+/// RFC 4648 base32 without padding, case-insensitive.
+fn from_base32(s: &str) -> Option<Vec<u8>> {
+    let mut res = Vec::with_capacity(s.len() * 5 / 8);
+    let mut buffer = 0u64;
+    let mut bits = 0;
+    for c in s.bytes() {
+        let value = match c.to_ascii_uppercase() {
+            c @ b'A'..=b'Z' => c - b'A',
+            c @ b'2'..=b'7' => c - b'2' + 26,
+            _ => return None,
+        };
+        buffer = (buffer << 5) | value as u64;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            res.push((buffer >> bits) as u8);
+        }
+    }
+    // Leftover bits must be zero padding of the last byte.
+    (bits < 5 && buffer & ((1 << bits) - 1) == 0).then_some(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bencode;
+    use crate::metainfo::read::from_bytes;
+
+    const HEX: &str = "c9e15763f722f23e98a29decdfae341b98d53056";
+    const BASE32: &str = "ZHQVOY7XELZD5GFCTXWN7LRUDOMNKMCW";
+    const V2: &str = "caf1e1c30e81cb361b9ee167c4aa64228a7fa4fa9f6105232b28ad099f3a302e";
+
+    #[test]
+    fn test_parse() {
+        let magnet: Magnet = format!(
+            "magnet:?xt=urn:btih:{}&dn=Big+Buck%20Bunny\
+             &tr=udp%3A%2F%2Ftracker.example%3A1337&tr.1=http://t2/announce\
+             &ws=https%3A%2F%2Fseed%2F&x.pe=10.0.0.1:6881&so=0,2,4-6&x.foo=bar",
+            HEX
+        )
+        .parse()
+        .unwrap();
+        assert_eq!(to_hex(&magnet.info_hash_v1.unwrap()), HEX);
+        assert_eq!(magnet.info_hash_v2, None);
+        assert_eq!(magnet.display_name.as_deref(), Some("Big Buck Bunny"));
+        assert_eq!(
+            magnet.trackers,
+            ["udp://tracker.example:1337", "http://t2/announce"]
+        );
+        assert_eq!(magnet.web_seeds, ["https://seed/"]);
+        assert_eq!(magnet.peers, ["10.0.0.1:6881"]);
+        assert_eq!(magnet.select_only, [0..=0, 2..=2, 4..=6]);
+        assert!(magnet.is_selected(5) && !magnet.is_selected(3));
+
+        let magnet: Magnet = format!("magnet:?xt=urn:btih:{}", BASE32.to_lowercase())
+            .parse()
+            .unwrap();
+        assert_eq!(to_hex(&magnet.info_hash_v1.unwrap()), HEX);
+        assert!(magnet.is_selected(3));
+
+        let magnet: Magnet = format!("MAGNET:?xt.1=urn:btih:{}&xt.2=urn:btmh:1220{}", HEX, V2)
+            .parse()
+            .unwrap();
+        assert_eq!(to_hex(&magnet.info_hash_v1.unwrap()), HEX);
+        assert_eq!(to_hex(&magnet.info_hash_v2.unwrap()), V2);
+    }
+
+    #[test]
+    fn test_errors() {
+        let parse = |s: &str| s.parse::<Magnet>().unwrap_err();
+        assert_eq!(parse("http://example"), MagnetError::NotMagnet);
+        assert_eq!(parse("magnet:?dn=a"), MagnetError::MissingInfoHash);
+        assert!(matches!(
+            parse("magnet:?xt=urn:btih:abc"),
+            MagnetError::InvalidInfoHash(_)
+        ));
+        // A SHA-1 multihash.
+        assert!(matches!(
+            parse(&format!("magnet:?xt=urn:btmh:1114{}", HEX)),
+            MagnetError::InvalidInfoHash(_)
+        ));
+        assert!(matches!(
+            parse("magnet:?xt=urn:btih:%4"),
+            MagnetError::InvalidEscape(_)
+        ));
+    }
+
+    #[test]
+    fn test_invalid_optional_parameters() {
+        let magnet: Magnet = format!(
+            "magnet:?xt=urn:btih:{}&dn=%4&tr=%ff&tr=http://t/&x.foo=%zz&kt=%&so=3-1&so=&so=2",
+            HEX
+        )
+        .parse()
+        .unwrap();
+        assert_eq!(to_hex(&magnet.info_hash_v1.unwrap()), HEX);
+        assert_eq!(magnet.display_name, None);
+        assert_eq!(magnet.trackers, ["http://t/"]);
+        assert_eq!(magnet.select_only, [2..=2]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let magnet = Magnet {
+            info_hash_v1: Some(from_hex(HEX).unwrap().try_into().unwrap()),
+            info_hash_v2: Some(from_hex(V2).unwrap().try_into().unwrap()),
+            display_name: Some("a b/ü".to_owned()),
+            trackers: vec!["http://t/a?x=1&y=2".to_owned()],
+            web_seeds: vec!["http://w/".to_owned()],
+            peers: vec!["[::1]:6881".to_owned()],
+            select_only: vec![1..=1, 3..=5],
+        };
+        let uri = magnet.to_string();
+        assert_eq!(
+            uri,
+            format!(
+                "magnet:?xt=urn:btih:{}&xt=urn:btmh:1220{}&dn=a%20b%2F%C3%BC\
+                 &tr=http%3A%2F%2Ft%2Fa%3Fx%3D1%26y%3D2&ws=http%3A%2F%2Fw%2F\
+                 &x.pe=%5B%3A%3A1%5D%3A6881&so=1,3-5",
+                HEX, V2
+            )
+        );
+        assert_eq!(uri.parse::<Magnet>().unwrap(), magnet);
+    }
+
+    #[test]
+    fn test_to_magnet() {
+        let source = bencode! {
+            "announce" => "http://a",
+            "announce-list" => [["http://a"], ["http://b", "http://c"]],
+            "info" => { "length" => 1, "name" => "file", "piece length" => 16384, "pieces" => &[0u8; 20] },
+        }
+        .to_bytes();
+        let meta = from_bytes(&source).unwrap();
+        let magnet = meta.to_magnet();
+        assert_eq!(magnet.info_hash_v1, meta.info_hash_v1());
+        assert_eq!(magnet.info_hash_v2, None);
+        assert_eq!(magnet.display_name.as_deref(), Some("file"));
+        assert_eq!(magnet.trackers, ["http://a", "http://b", "http://c"]);
+        assert_eq!(magnet.to_string().parse::<Magnet>().unwrap(), magnet);
+    }
+}
@@ -0,0 +1,395 @@
+//! BEP 52 merkle trees: SHA-256 over 16 KiB blocks.
+
+use std::fmt;
+
+use sha2::{Digest, Sha256};
+
+use super::Sha256Hash;
+
+pub const BLOCK_SIZE: usize = 16 * 1024;
+
+/// Stands in for leaves past the end of a file.
+pub const ZERO_HASH: Sha256Hash = [0; 32];
+
+/// The most hashes a BEP 52 `hash request` may ask for.
+pub const MAX_PROOF_LENGTH: usize = 512;
+
+pub fn hash_block(block: &[u8]) -> Sha256Hash {
+    Sha256::digest(block).into()
+}
+
+pub fn hash_pair(left: &Sha256Hash, right: &Sha256Hash) -> Sha256Hash {
+    let mut hasher = Sha256::new();
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Root of a subtree covering one piece of `piece_length` bytes that is
+/// beyond the end of the file.
+pub fn zero_piece_root(piece_length: u64) -> Sha256Hash {
+    MerkleTree::new(Vec::new(), blocks_per_piece(piece_length), ZERO_HASH).root()
+}
+
+pub fn blocks_per_piece(piece_length: u64) -> usize {
+    (piece_length as usize / BLOCK_SIZE).max(1)
+}
+
+/// A merkle tree whose bottom layer is some hashes followed by copies of a
+/// padding hash up to a power of two. Only nodes over the hashes are
+/// stored; the rest of each layer is the padding of that layer, so wide
+/// trees over few hashes stay small.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleTree {
+    /// From the bottom layer up to the root, each half as wide as the one
+    /// below.
+    layers: Vec<Vec<Sha256Hash>>,
+    /// The padding of every layer.
+    padding: Vec<Sha256Hash>,
+}
+
+impl MerkleTree {
+    /// A tree over `hashes` padded with `padding` to `width` nodes. `width`
+    /// must be a power of two no smaller than `hashes.len()`.
+    ///
+    /// `padding` is [`ZERO_HASH`] when `hashes` are leaves, and the root of
+    /// an all-zero subtree when they are inner nodes, such as the piece
+    /// layer.
+    pub fn new(hashes: Vec<Sha256Hash>, width: usize, padding: Sha256Hash) -> MerkleTree {
+        assert!(width.is_power_of_two() && hashes.len() <= width);
+        let mut tree = MerkleTree {
+            layers: vec![hashes],
+            padding: vec![padding],
+        };
+        for level in 0..width.trailing_zeros() as usize {
+            let padding = tree.padding[level];
+            let layer = tree.layers[level]
+                .chunks(2)
+                .map(|pair| hash_pair(&pair[0], pair.get(1).unwrap_or(&padding)))
+                .collect();
+            tree.layers.push(layer);
+            tree.padding.push(hash_pair(&padding, &padding));
+        }
+        tree
+    }
+
+    /// A tree over the leaves of a file, padded with [`ZERO_HASH`].
+    pub fn from_leaves(leaves: Vec<Sha256Hash>) -> MerkleTree {
+        let width = leaves.len().next_power_of_two();
+        MerkleTree::new(leaves, width, ZERO_HASH)
+    }
+
+    pub fn from_data(data: &[u8]) -> MerkleTree {
+        let mut builder = MerkleBuilder::new();
+        builder.update(data);
+        builder.finish()
+    }
+
+    /// The `pieces root` of the file.
+    pub fn root(&self) -> Sha256Hash {
+        self.node(self.layers.len() - 1, 0)
+            .expect("the top layer has one node")
+    }
+
+    /// Number of hashes the tree was built over, not counting padding.
+    pub fn leaf_count(&self) -> usize {
+        self.layers[0].len()
+    }
+
+    /// Number of layers, leaves and root included.
+    pub fn height(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// The nodes of a layer over actual data, without padding; 0 is the
+    /// leaves.
+    pub fn layer(&self, level: usize) -> Option<&[Sha256Hash]> {
+        self.layers.get(level).map(Vec::as_slice)
+    }
+
+    /// A node of layer `level`, padding included.
+    pub fn node(&self, level: usize, index: usize) -> Option<Sha256Hash> {
+        let height = self.layers.len();
+        if level >= height || index >= 1 << (height - 1 - level) {
+            return None;
+        }
+        Some(
+            *self.layers[level]
+                .get(index)
+                .unwrap_or(&self.padding[level]),
+        )
+    }
+
+    /// The hashes stored in `piece layers`: one per piece of the file,
+    /// without padding. `None` for files that fit in a single piece, whose
+    /// root is all that is needed.
+    pub fn piece_layer(&self, piece_length: u64) -> Option<&[Sha256Hash]> {
+        let blocks = blocks_per_piece(piece_length);
+        let level = blocks.trailing_zeros() as usize;
+        if self.leaf_count() <= blocks || level + 1 >= self.layers.len() {
+            return None;
+        }
+        Some(&self.layers[level])
+    }
+
+    /// `length` hashes of layer `level` starting at `index`, followed by
+    /// up to `proof_layers` uncle hashes proving them, lowest first, as in
+    /// the BEP 52 `hashes` message. `length` must be a power of two of at
+    /// most [`MAX_PROOF_LENGTH`] and `index` a multiple of it. The root
+    /// itself is never included.
+    pub fn proof(
+        &self,
+        level: usize,
+        index: usize,
+        length: usize,
+        proof_layers: usize,
+    ) -> Option<Vec<Sha256Hash>> {
+        if !length.is_power_of_two() || length > MAX_PROOF_LENGTH || !index.is_multiple_of(length) {
+            return None;
+        }
+        let mut hashes = (index..index.checked_add(length)?)
+            .map(|index| self.node(level, index))
+            .collect::<Option<Vec<_>>>()?;
+        let mut level = level + length.trailing_zeros() as usize;
+        let mut position = index / length;
+        while hashes.len() - length < proof_layers && level + 1 < self.layers.len() {
+            hashes.push(self.node(level, position ^ 1)?);
+            level += 1;
+            position /= 2;
+        }
+        Some(hashes)
+    }
+}
+
+/// [`MerkleBuilder::push_leaf`] was called with part of a block fed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartialBlockError;
+
+impl fmt::Display for PartialBlockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a partial block is pending")
+    }
+}
+
+impl std::error::Error for PartialBlockError {}
+
+/// Builds a [`MerkleTree`] from data fed in pieces of any size.
+#[derive(Debug, Clone, Default)]
+pub struct MerkleBuilder {
+    leaves: Vec<Sha256Hash>,
+    block: Vec<u8>,
+}
+
+impl MerkleBuilder {
+    pub fn new() -> MerkleBuilder {
+        MerkleBuilder::default()
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let length = (BLOCK_SIZE - self.block.len()).min(data.len());
+            self.block.extend_from_slice(&data[..length]);
+            data = &data[length..];
+            if self.block.len() == BLOCK_SIZE {
+                self.leaves.push(hash_block(&self.block));
+                self.block.clear();
+            }
+        }
+    }
+
+    /// Adds the hash of a full block, or of the last block of the file.
+    /// Fails if data fed with [`update`](MerkleBuilder::update) does not
+    /// end on a block boundary.
+    pub fn push_leaf(&mut self, leaf: Sha256Hash) -> Result<(), PartialBlockError> {
+        if !self.block.is_empty() {
+            return Err(PartialBlockError);
+        }
+        self.leaves.push(leaf);
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> MerkleTree {
+        if !self.block.is_empty() {
+            self.leaves.push(hash_block(&self.block));
+        }
+        MerkleTree::from_leaves(self.leaves)
+    }
+}
+
+/// Checks hashes as returned by [`MerkleTree::proof`]: the first `length`
+/// hashes, starting at `index` in their layer, are combined with the uncle
+/// hashes that follow, and the result must equal `expected`. That is the
+/// `pieces root` when the proof goes up to the root, or a hash of a lower
+/// layer, such as the piece layer, that is already trusted.
+pub fn verify_proof(
+    hashes: &[Sha256Hash],
+    index: usize,
+    length: usize,
+    expected: &Sha256Hash,
+) -> bool {
+    if !length.is_power_of_two() || !index.is_multiple_of(length) || hashes.len() < length {
+        return false;
+    }
+    let (range, uncles) = hashes.split_at(length);
+    let mut node = MerkleTree::new(range.to_vec(), length, ZERO_HASH).root();
+    let mut position = index / length;
+    for uncle in uncles {
+        node = if position.is_multiple_of(2) {
+            hash_pair(&node, uncle)
+        } else {
+            hash_pair(uncle, &node)
+        };
+        position /= 2;
+    }
+    &node == expected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bencoding::utils::to_hex;
+    use crate::metainfo::read::from_bytes;
+    use crate::metainfo::test_util::{v2_torrent_files, V2_TORRENT};
+
+    /// 5 blocks and 100 bytes of `i % 251`.
+    fn data() -> Vec<u8> {
+        (0..5 * BLOCK_SIZE + 100).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn root(hashes: &[Sha256Hash], width: usize, padding: Sha256Hash) -> Sha256Hash {
+        MerkleTree::new(hashes.to_vec(), width, padding).root()
+    }
+
+    #[test]
+    fn test_root() {
+        let a = hash_block(b"a");
+        let b = hash_block(b"b");
+        assert_eq!(root(&[a], 1, ZERO_HASH), a);
+        assert_eq!(root(&[a, b], 2, ZERO_HASH), hash_pair(&a, &b));
+        let zero_pair = hash_pair(&ZERO_HASH, &ZERO_HASH);
+        assert_eq!(
+            root(&[a], 4, ZERO_HASH),
+            hash_pair(&hash_pair(&a, &ZERO_HASH), &zero_pair)
+        );
+        assert_eq!(root(&[], 4, ZERO_HASH), hash_pair(&zero_pair, &zero_pair));
+        assert_eq!(
+            zero_piece_root(4 * BLOCK_SIZE as u64),
+            root(&[], 4, ZERO_HASH)
+        );
+
+        // Padding is not stored, so wide trees over few hashes are cheap.
+        let tree = MerkleTree::new(vec![a], 1 << 40, ZERO_HASH);
+        assert_eq!(tree.height(), 41);
+        assert_eq!(tree.layer(1).unwrap(), [hash_pair(&a, &ZERO_HASH)]);
+        assert_eq!(tree.node(0, (1 << 40) - 1), Some(ZERO_HASH));
+        assert_eq!(tree.node(0, 1 << 40), None);
+    }
+
+    #[test]
+    fn test_vectors() {
+        assert_eq!(
+            to_hex(&hash_block(b"hello")),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(
+            to_hex(&hash_block(&[0; BLOCK_SIZE])),
+            "4fe7b59af6de3b665b67788cc2f99892ab827efae3a467342b3bb4e3bc8e5bfe"
+        );
+
+        let tree = MerkleTree::from_data(&data());
+        assert_eq!(tree.leaf_count(), 6);
+        assert_eq!(tree.height(), 4);
+        assert_eq!(
+            to_hex(&tree.layer(0).unwrap()[0]),
+            "4348e3b98e8a327b34ced39c1da9e67cdb4cd5e48e4d7960607a3ae403d35f0c"
+        );
+        assert_eq!(
+            to_hex(&tree.root()),
+            "d3aca1dcbe82ef01623077044a71db5130550ac63a7b5da35a6abcf8a30c6e00"
+        );
+        let piece_layer = tree.piece_layer(2 * BLOCK_SIZE as u64).unwrap();
+        assert_eq!(
+            piece_layer.iter().map(|h| to_hex(h)).collect::<Vec<_>>(),
+            [
+                "d9e13d0b676ad681164ef0b7b5910d1328ea83a047cad57e619d76bbe3a08525",
+                "e28097eaaa55956702cf8195d1a551dbabb63e3d679b294cf33d506a6b5ef479",
+                "aa7c12b95f2ca9c9c8f9621df8c9d89d6e7159c2cec3b0a98a86371637c04ab7",
+            ]
+        );
+        let padding = zero_piece_root(2 * BLOCK_SIZE as u64);
+        assert_eq!(root(piece_layer, 4, padding), tree.root());
+        assert_eq!(tree.piece_layer(8 * BLOCK_SIZE as u64), None);
+    }
+
+    #[test]
+    fn test_builder() {
+        let data = data();
+        let mut builder = MerkleBuilder::new();
+        for chunk in data.chunks(5000) {
+            builder.update(chunk);
+        }
+        assert_eq!(builder.finish(), MerkleTree::from_data(&data));
+
+        let mut builder = MerkleBuilder::new();
+        builder.push_leaf(hash_block(b"a")).unwrap();
+        assert_eq!(builder.finish().root(), hash_block(b"a"));
+        let mut builder = MerkleBuilder::new();
+        builder.update(b"a");
+        assert_eq!(builder.push_leaf(hash_block(b"b")), Err(PartialBlockError));
+        assert_eq!(MerkleTree::from_data(&[]).root(), ZERO_HASH);
+    }
+
+    #[test]
+    fn test_proof() {
+        let tree = MerkleTree::from_data(&data());
+        let root = tree.root();
+        for index in 0..8 {
+            let proof = tree.proof(0, index, 1, 3).unwrap();
+            assert_eq!(proof.len(), 4);
+            assert!(verify_proof(&proof, index, 1, &root));
+            // Past the data both siblings are ZERO_HASH.
+            assert_eq!(verify_proof(&proof, index ^ 1, 1, &root), index >= 6);
+        }
+
+        // Two blocks checked against a trusted piece layer hash.
+        let piece_layer = tree.piece_layer(2 * BLOCK_SIZE as u64).unwrap();
+        let proof = tree.proof(0, 2, 2, 0).unwrap();
+        assert_eq!(proof.len(), 2);
+        assert!(verify_proof(&proof, 2, 2, &piece_layer[1]));
+
+        // The piece layer itself, proven up to the root.
+        let proof = tree.proof(1, 0, 4, 8).unwrap();
+        assert_eq!(proof.len(), 4);
+        assert!(verify_proof(&proof, 0, 4, &root));
+        let mut tampered = tree.proof(0, 5, 1, 3).unwrap();
+        tampered[2][0] ^= 1;
+        assert!(!verify_proof(&tampered, 5, 1, &root));
+
+        assert_eq!(tree.proof(0, 1, 2, 3), None);
+        assert_eq!(tree.proof(0, 8, 1, 3), None);
+        assert_eq!(tree.proof(4, 0, 1, 0), None);
+        assert_eq!(tree.proof(0, 0, 1024, 0), None);
+        assert_eq!(tree.proof(0, usize::MAX - 1, 2, 0), None);
+        let tree = MerkleTree::new(Vec::new(), 1 << 40, ZERO_HASH);
+        assert_eq!(tree.proof(0, 0, MAX_PROOF_LENGTH, 0).unwrap().len(), 512);
+        assert_eq!(tree.proof(0, 1 << 40, 512, 0), None);
+    }
+
+    #[test]
+    fn test_v2_torrent() {
+        let meta = from_bytes(V2_TORRENT).unwrap();
+        let files = meta.info.v2.as_ref().unwrap().file_tree.files();
+        let contents = v2_torrent_files();
+        assert_eq!(files.len(), contents.len());
+        for ((path, file), (expected_path, data)) in files.iter().zip(&contents) {
+            assert_eq!(&path.join("/"), expected_path);
+            let tree = MerkleTree::from_data(data);
+            assert_eq!(file.pieces_root, Some(tree.root()));
+            assert_eq!(
+                tree.piece_layer(meta.info.piece_length),
+                meta.piece_layers.get(&tree.root()).map(Vec::as_slice)
+            );
+        }
+        assert_eq!(meta.piece_layers.len(), 1);
+    }
+}
@@ -1,26 +1,184 @@
-use crate::bencoding::value::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+
+pub mod create;
+mod hashing;
+pub mod hybrid;
 pub mod keys;
+pub mod magnet;
+pub mod merkle;
 pub mod read;
+#[cfg(test)]
+mod test_util;
+pub mod trackers;
+pub mod verify;
+pub mod write;
+
+pub type Sha1Hash = [u8; 20];
+pub type Sha256Hash = [u8; 32];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct File {
+    pub length: u64,
+    /// Path components relative to the torrent directory, the last one being the file name.
+    pub path: Vec<String>,
+    /// BEP 47 attributes, e.g. `p` for padding files or `x` for executables.
+    pub attr: Option<String>,
+}
+
+impl File {
+    /// Padding files (BEP 47) only align the next file to a piece boundary
+    /// and are not part of the content.
+    pub fn is_padding(&self) -> bool {
+        self.attr.as_deref().is_some_and(|attr| attr.contains('p'))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Files {
+    /// A single file named after `Info::name`.
+    Single { length: u64 },
+    /// Several files inside a directory named after `Info::name`.
+    Multiple(Vec<File>),
+}
+
+impl Files {
+    /// Length of the v1 torrent data, padding files included.
+    pub fn total_length(&self) -> u64 {
+        match self {
+            Files::Single { length } => *length,
+            Files::Multiple(files) => files.iter().map(|f| f.length).sum(),
+        }
+    }
+}
+
+/// A file leaf of the BEP 52 `file tree`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeFile {
+    pub length: u64,
+    /// Root of the file's merkle tree, absent for empty files.
+    pub pieces_root: Option<Sha256Hash>,
+}
+
+/// BEP 52 `file tree`: directories map path components to subtrees.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileTree {
+    File(TreeFile),
+    Directory(BTreeMap<String, FileTree>),
+}
+
+impl FileTree {
+    /// Returns every file of the tree with its path components, in the
+    /// order of the (sorted) keys.
+    pub fn files(&self) -> Vec<(Vec<String>, &TreeFile)> {
+        let mut files = Vec::new();
+        self.collect_files(&mut Vec::new(), &mut files);
+        files
+    }
 
-#[derive(Debug)]
-pub enum FileTree {}
+    fn collect_files<'t>(
+        &'t self,
+        path: &mut Vec<String>,
+        out: &mut Vec<(Vec<String>, &'t TreeFile)>,
+    ) {
+        match self {
+            FileTree::File(file) => out.push((path.clone(), file)),
+            FileTree::Directory(entries) => {
+                for (name, tree) in entries {
+                    path.push(name.clone());
+                    tree.collect_files(path, out);
+                    path.pop();
+                }
+            }
+        }
+    }
 
-#[derive(Debug)]
+    pub fn total_length(&self) -> u64 {
+        match self {
+            FileTree::File(file) => file.length,
+            FileTree::Directory(entries) => entries.values().map(FileTree::total_length).sum(),
+        }
+    }
+}
+
+/// BEP 3 part of the info dictionary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InfoV1 {
+    pub pieces: Vec<Sha1Hash>,
+    pub files: Files,
+}
+
+/// BEP 52 part of the info dictionary (`meta version` 2).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InfoV2 {
+    pub file_tree: FileTree,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Info {
     pub name: String,
     pub piece_length: u64,
-    pub meta_version: u64,
-    pub file_tree: FileTree,
-    pub length: u64,
-    pub pieces_root: Vec<u8>,
-    pub private: Option<u64>,
+    pub private: bool,
+    pub v1: Option<InfoV1>,
+    pub v2: Option<InfoV2>,
+}
+
+impl Info {
+    /// Hybrid torrents (BEP 52) describe the same content with both the v1
+    /// and the v2 metadata.
+    pub fn is_hybrid(&self) -> bool {
+        self.v1.is_some() && self.v2.is_some()
+    }
+
+    pub fn total_length(&self) -> u64 {
+        match (&self.v1, &self.v2) {
+            (Some(v1), _) => v1.files.total_length(),
+            (None, Some(v2)) => v2.file_tree.total_length(),
+            (None, None) => 0,
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MetaInfo {
-    pub announce: String,
+    pub announce: Option<String>,
+    /// BEP 12 tiers of tracker URLs, empty when `announce-list` is absent.
+    pub announce_list: Vec<Vec<String>>,
     pub info: Info,
-    pub piece_layers: HashMap<String, Value>,
+    /// Maps a file's `pieces root` to the hashes of its merkle tree layer
+    /// at piece granularity. Empty for v1 torrents.
+    pub piece_layers: HashMap<Sha256Hash, Vec<Sha256Hash>>,
+    pub creation_date: Option<i64>,
+    pub comment: Option<String>,
+    pub created_by: Option<String>,
+    pub encoding: Option<String>,
+    /// The `info` dictionary exactly as it appears in the .torrent file.
+    pub raw_info: Vec<u8>,
+}
+
+impl MetaInfo {
+    /// SHA-1 of the `info` dictionary, identifying v1 and hybrid torrents.
+    pub fn info_hash_v1(&self) -> Option<Sha1Hash> {
+        self.info
+            .v1
+            .as_ref()
+            .map(|_| Sha1::digest(&self.raw_info).into())
+    }
+
+    /// SHA-256 of the `info` dictionary, identifying v2 and hybrid torrents.
+    pub fn info_hash_v2(&self) -> Option<Sha256Hash> {
+        self.info
+            .v2
+            .as_ref()
+            .map(|_| Sha256::digest(&self.raw_info).into())
+    }
+
+    /// The v2 infohash truncated to 20 bytes, as used by the peer wire
+    /// protocol and DHT.
+    pub fn info_hash_v2_truncated(&self) -> Option<Sha1Hash> {
+        self.info_hash_v2()
+            .map(|hash| hash[..20].try_into().unwrap())
+    }
 }
@@ -1,78 +1,794 @@
+use std::fmt::Display;
 use std::io::Read;
 
+use std::collections::{BTreeMap, HashMap};
+
+use super::keys::*;
+use super::trackers::parse_announce_list;
+use super::{
+    File, FileTree, Files, Info, InfoV1, InfoV2, MetaInfo, Sha1Hash, Sha256Hash, TreeFile,
+};
 use crate::bencoding::{
-    parse::try_parse_value,
+    integer::IntegerError,
+    parse::{find_span, try_parse_value, ParseError},
+    utils::to_hex,
     value::{Value, ValueType},
 };
 
-/*
-interface IMetaInfo {
-    announce: string
-    announce-list: string[][]
-    azureus_properties?: {
-        dht_backup_enable: 1 | 0
+#[derive(Debug)]
+pub enum ReadError {
+    Io(std::io::Error),
+    Parse(ParseError),
+    MissingKey(String),
+    UnexpectedType {
+        path: String,
+        expected: ValueType,
+        found: ValueType,
+    },
+    NegativeInteger(String),
+    /// An integer too large for `i64`.
+    IntegerOutOfRange(String),
+    InvalidUtf8(String),
+    /// `pieces` length is not a multiple of 20.
+    InvalidPiecesLength(usize),
+    /// `info` contains both `length` and `files`.
+    AmbiguousFiles,
+    /// `info` contains neither `length` nor `files`.
+    MissingFiles,
+    EmptyPath(String),
+    /// `meta version` is present but is not 2.
+    UnsupportedMetaVersion(u64),
+    /// Zero, or for v2 not a power of two of at least 16 KiB.
+    InvalidPieceLength(u64),
+    /// `pieces` does not hold one hash per piece of the files.
+    InvalidPieceCount {
+        expected: u64,
+        found: usize,
+    },
+    /// The file lengths add up to more than `u64::MAX` bytes.
+    LengthOverflow,
+    /// A hash, or a concatenation of hashes, has an invalid length.
+    InvalidHashLength {
+        path: String,
+        length: usize,
+    },
+    /// A `file tree` node is neither a file nor a non-empty directory.
+    InvalidFileTree(String),
+    /// A file larger than a piece has no entry in `piece layers`.
+    MissingPieceLayer(String),
+    /// A `piece layers` entry does not hold the expected number of hashes.
+    InvalidPieceLayer {
+        path: String,
+        expected: usize,
+        found: usize,
     },
-    privage: 1 | 0,
-    "creation date": number // e.g 1608033138
-    comment: string,
-    info: {
-        length: number, // 36_947_471_188
-        name: string,
-        "piece length": number,
-        pieces: string
-        "file-duration": unknown,
-        "profiles": unknown,
+}
+
+impl Display for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadError::Io(e) => write!(f, "{}", e),
+            ReadError::Parse(e) => write!(f, "invalid bencode: {}", e),
+            ReadError::MissingKey(path) => write!(f, "{} is missing", path),
+            ReadError::UnexpectedType {
+                path,
+                expected,
+                found,
+            } => write!(f, "{} is {:?}, expected {:?}", path, found, expected),
+            ReadError::NegativeInteger(path) => write!(f, "{} must not be negative", path),
+            ReadError::IntegerOutOfRange(path) => write!(f, "{} is too large", path),
+            ReadError::InvalidUtf8(path) => write!(f, "{} is not valid UTF-8", path),
+            ReadError::InvalidPiecesLength(len) => write!(
+                f,
+                "['info']['pieces'] has length {} which is not a multiple of 20",
+                len
+            ),
+            ReadError::AmbiguousFiles => {
+                write!(f, "['info'] contains both 'length' and 'files'")
+            }
+            ReadError::MissingFiles => write!(f, "['info'] contains neither 'length' nor 'files'"),
+            ReadError::EmptyPath(path) => write!(f, "{} is empty", path),
+            ReadError::UnsupportedMetaVersion(version) => {
+                write!(f, "meta version {} is not supported", version)
+            }
+            ReadError::InvalidPieceLength(0) => write!(f, "piece length must not be 0"),
+            ReadError::InvalidPieceLength(length) => write!(
+                f,
+                "piece length {} is not a power of two of at least 16 KiB",
+                length
+            ),
+            ReadError::InvalidPieceCount { expected, found } => write!(
+                f,
+                "['info']['pieces'] has {} hashes, expected {}",
+                found, expected
+            ),
+            ReadError::LengthOverflow => write!(f, "the file lengths add up to too many bytes"),
+            ReadError::InvalidHashLength { path, length } => {
+                write!(f, "{} has invalid hash length {}", path, length)
+            }
+            ReadError::InvalidFileTree(path) => write!(f, "{} is not a valid file tree", path),
+            ReadError::MissingPieceLayer(path) => {
+                write!(f, "piece layer of file '{}' is missing", path)
+            }
+            ReadError::InvalidPieceLayer {
+                path,
+                expected,
+                found,
+            } => write!(f, "{} has {} hashes, expected {}", path, found, expected),
+        }
     }
+}
+
+impl std::error::Error for ReadError {}
 
+impl From<std::io::Error> for ReadError {
+    fn from(e: std::io::Error) -> Self {
+        ReadError::Io(e)
+    }
 }
-*/
 
-fn shorten(s: &str) -> String {
-    if s.len() <= 80 {
-        s.to_owned()
-    } else {
-        let start = s.chars().take(20).collect::<String>();
-        let end = s.chars().skip(s.len() - 20).collect::<String>();
+impl From<ParseError> for ReadError {
+    fn from(e: ParseError) -> Self {
+        ReadError::Parse(e)
+    }
+}
 
-        format!("{}...{}", start, end)
+pub type ReadResult<T> = std::result::Result<T, ReadError>;
+
+fn unexpected_type(value: &Value, path: &str, expected: ValueType) -> ReadError {
+    ReadError::UnexpectedType {
+        path: path.to_owned(),
+        expected,
+        found: value.get_type(),
     }
 }
 
-fn recursive_print(value: &Value, prefix: &str) {
-    match value.get_type() {
-        ValueType::String => {
-            println!("{} = '{}'", prefix, shorten(&value.to_lossy_str().unwrap()));
+fn as_bytes<'v>(value: &'v Value, path: &str) -> ReadResult<&'v [u8]> {
+    match value {
+        Value::String(bytes) => Ok(bytes),
+        _ => Err(unexpected_type(value, path, ValueType::String)),
+    }
+}
+
+fn as_string(value: &Value, path: &str) -> ReadResult<String> {
+    to_string(as_bytes(value, path)?, path)
+}
+
+fn to_string(bytes: &[u8], path: &str) -> ReadResult<String> {
+    String::from_utf8(bytes.to_vec()).map_err(|_| ReadError::InvalidUtf8(path.to_owned()))
+}
+
+fn as_integer(value: &Value, path: &str) -> ReadResult<i64> {
+    value.as_i64().map_err(|e| match e {
+        IntegerError::OutOfRange => ReadError::IntegerOutOfRange(path.to_owned()),
+        _ => unexpected_type(value, path, ValueType::Integer),
+    })
+}
+
+fn as_unsigned(value: &Value, path: &str) -> ReadResult<u64> {
+    let i = as_integer(value, path)?;
+    u64::try_from(i).map_err(|_| ReadError::NegativeInteger(path.to_owned()))
+}
+
+fn as_list<'v>(value: &'v Value, path: &str) -> ReadResult<&'v [Value]> {
+    match value {
+        Value::List(values) => Ok(values),
+        _ => Err(unexpected_type(value, path, ValueType::List)),
+    }
+}
+
+/// A dictionary together with its location inside the document, used to
+/// produce readable errors.
+struct Dict<'v> {
+    value: &'v Value,
+    path: String,
+}
+
+impl<'v> Dict<'v> {
+    fn new(value: &'v Value, path: String) -> ReadResult<Self> {
+        match value {
+            Value::Dictionary(_) => Ok(Dict { value, path }),
+            _ => Err(unexpected_type(value, &path, ValueType::Dictionary)),
         }
-        ValueType::Integer => {
-            let int = match value {
-                Value::Integer(n) => *n,
-                _ => unreachable!(),
-            };
-            println!("{} = {}", prefix, int);
+    }
+
+    fn path(&self, key: &str) -> String {
+        format!("{}['{}']", self.path, key)
+    }
+
+    fn optional(&self, key: &str) -> Option<&'v Value> {
+        self.value.get(&key.into())
+    }
+
+    fn required(&self, key: &str) -> ReadResult<&'v Value> {
+        self.optional(key)
+            .ok_or_else(|| ReadError::MissingKey(self.path(key)))
+    }
+
+    fn dict(&self, key: &str) -> ReadResult<Dict<'v>> {
+        Dict::new(self.required(key)?, self.path(key))
+    }
+
+    fn string(&self, key: &str) -> ReadResult<String> {
+        as_string(self.required(key)?, &self.path(key))
+    }
+
+    /// An optional text field, decoded lossily since free text such as
+    /// `comment` is often in the legacy charset named by `encoding`. Values
+    /// that are not strings are ignored.
+    fn lossy_string(&self, key: &str) -> Option<String> {
+        self.optional(key)?.to_lossy_str().map(|s| s.into_owned())
+    }
+
+    /// An optional string that is only useful as valid UTF-8, such as a URL.
+    /// Anything else is ignored.
+    fn lenient_string(&self, key: &str) -> Option<String> {
+        match self.optional(key)? {
+            Value::String(bytes) => String::from_utf8(bytes.clone()).ok(),
+            _ => None,
         }
-        ValueType::List => {
-            for (ind, v) in value.values().enumerate() {
-                let new_prefix = format!("{}[{}]", prefix, ind);
-                recursive_print(v, &new_prefix);
-            }
+    }
+
+    fn unsigned(&self, key: &str) -> ReadResult<u64> {
+        as_unsigned(self.required(key)?, &self.path(key))
+    }
+
+    /// An optional integer, ignored when it is not an integer or too large.
+    fn lenient_integer(&self, key: &str) -> Option<i64> {
+        self.optional(key)?.as_i64().ok()
+    }
+}
+
+fn as_hash<const N: usize>(value: &Value, path: &str) -> ReadResult<[u8; N]> {
+    to_hash(as_bytes(value, path)?, path)
+}
+
+fn to_hash<const N: usize>(bytes: &[u8], path: &str) -> ReadResult<[u8; N]> {
+    bytes.try_into().map_err(|_| ReadError::InvalidHashLength {
+        path: path.to_owned(),
+        length: bytes.len(),
+    })
+}
+
+fn as_hashes<const N: usize>(value: &Value, path: &str) -> ReadResult<Vec<[u8; N]>> {
+    let bytes = as_bytes(value, path)?;
+    if bytes.len() % N != 0 {
+        return Err(ReadError::InvalidHashLength {
+            path: path.to_owned(),
+            length: bytes.len(),
+        });
+    }
+    Ok(bytes
+        .chunks_exact(N)
+        .map(|chunk| chunk.try_into().unwrap())
+        .collect())
+}
+
+fn read_pieces(info: &Dict) -> ReadResult<Vec<Sha1Hash>> {
+    let pieces = info.required(PIECES_KEY)?;
+    as_hashes(pieces, &info.path(PIECES_KEY)).map_err(|e| match e {
+        ReadError::InvalidHashLength { length, .. } => ReadError::InvalidPiecesLength(length),
+        e => e,
+    })
+}
+
+fn read_file(value: &Value, path: String) -> ReadResult<File> {
+    let file = Dict::new(value, path)?;
+    let length = file.unsigned(LENGTH_KEY)?;
+
+    let components_path = file.path(PATH_KEY);
+    let components = as_list(file.required(PATH_KEY)?, &components_path)?;
+    if components.is_empty() {
+        return Err(ReadError::EmptyPath(components_path));
+    }
+    let path = components
+        .iter()
+        .enumerate()
+        .map(|(ind, c)| as_string(c, &format!("{}[{}]", components_path, ind)))
+        .collect::<ReadResult<Vec<_>>>()?;
+
+    let attr = file.lossy_string(ATTR_KEY);
+
+    Ok(File { length, path, attr })
+}
+
+fn read_files(info: &Dict) -> ReadResult<Files> {
+    match (info.optional(LENGTH_KEY), info.optional(FILES_KEY)) {
+        (Some(_), Some(_)) => Err(ReadError::AmbiguousFiles),
+        (None, None) => Err(ReadError::MissingFiles),
+        (Some(length), None) => Ok(Files::Single {
+            length: as_unsigned(length, &info.path(LENGTH_KEY))?,
+        }),
+        (None, Some(files)) => {
+            let files_path = info.path(FILES_KEY);
+            let files = as_list(files, &files_path)?
+                .iter()
+                .enumerate()
+                .map(|(ind, f)| read_file(f, format!("{}[{}]", files_path, ind)))
+                .collect::<ReadResult<Vec<_>>>()?;
+            checked_total(files.iter().map(|f| f.length))?;
+            Ok(Files::Multiple(files))
+        }
+    }
+}
+
+/// Sums file lengths, failing instead of overflowing so that the total
+/// length accessors of an accepted torrent cannot overflow either.
+fn checked_total(lengths: impl IntoIterator<Item = u64>) -> ReadResult<u64> {
+    lengths
+        .into_iter()
+        .try_fold(0u64, |total, length| total.checked_add(length))
+        .ok_or(ReadError::LengthOverflow)
+}
+
+fn read_tree_file(value: &Value, path: String) -> ReadResult<TreeFile> {
+    let file = Dict::new(value, path)?;
+    let length = file.unsigned(LENGTH_KEY)?;
+    let pieces_root = match file.optional(PIECES_ROOT_KEY) {
+        Some(root) => Some(as_hash(root, &file.path(PIECES_ROOT_KEY))?),
+        None if length > 0 => return Err(ReadError::MissingKey(file.path(PIECES_ROOT_KEY))),
+        None => None,
+    };
+    Ok(TreeFile {
+        length,
+        pieces_root,
+    })
+}
+
+fn read_file_tree(value: &Value, path: String) -> ReadResult<FileTree> {
+    let node = Dict::new(value, path)?;
+    if let Some(file) = node.optional("") {
+        if node.value.len() != 1 {
+            return Err(ReadError::InvalidFileTree(node.path));
+        }
+        return read_tree_file(file, node.path("")).map(FileTree::File);
+    }
+
+    let mut entries = BTreeMap::new();
+    for (key, child) in node.value.entries() {
+        let name = to_string(key, &node.path)?;
+        if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+            return Err(ReadError::InvalidFileTree(node.path(&name)));
+        }
+        let child = read_file_tree(child, node.path(&name))?;
+        entries.insert(name, child);
+    }
+    if entries.is_empty() {
+        return Err(ReadError::InvalidFileTree(node.path));
+    }
+    Ok(FileTree::Directory(entries))
+}
+
+fn read_info_v2(info: &Dict) -> ReadResult<InfoV2> {
+    let file_tree = read_file_tree(info.required(FILE_TREE_KEY)?, info.path(FILE_TREE_KEY))?;
+    match file_tree {
+        FileTree::File(_) => Err(ReadError::InvalidFileTree(info.path(FILE_TREE_KEY))),
+        FileTree::Directory(_) => {
+            checked_total(file_tree.files().iter().map(|(_, file)| file.length))?;
+            Ok(InfoV2 { file_tree })
         }
-        ValueType::Dictionary => {
-            for (key, value) in value.entries() {
-                let new_prefix = format!("{}['{}']", prefix, key.to_lossy_str().unwrap());
-                recursive_print(value, &new_prefix);
+    }
+}
+
+fn read_info(info: &Dict) -> ReadResult<Info> {
+    let piece_length = info.unsigned(PIECE_LENGTH_KEY)?;
+    if piece_length == 0 {
+        return Err(ReadError::InvalidPieceLength(piece_length));
+    }
+
+    let meta_version = info
+        .optional(META_VERSION_KEY)
+        .map(|v| as_unsigned(v, &info.path(META_VERSION_KEY)))
+        .transpose()?;
+    let v2 = match meta_version {
+        None => None,
+        Some(2) => {
+            if !piece_length.is_power_of_two() || piece_length < 16 * 1024 {
+                return Err(ReadError::InvalidPieceLength(piece_length));
             }
+            Some(read_info_v2(info)?)
+        }
+        Some(version) => return Err(ReadError::UnsupportedMetaVersion(version)),
+    };
+
+    // v2-only torrents carry no `pieces`; anything else must be a valid v1 torrent.
+    let v1 = if v2.is_none() || info.optional(PIECES_KEY).is_some() {
+        let pieces = read_pieces(info)?;
+        let files = read_files(info)?;
+        let expected = files.total_length().div_ceil(piece_length);
+        if pieces.len() as u64 != expected {
+            return Err(ReadError::InvalidPieceCount {
+                expected,
+                found: pieces.len(),
+            });
+        }
+        Some(InfoV1 { pieces, files })
+    } else {
+        None
+    };
+
+    Ok(Info {
+        name: info.string(NAME_KEY)?,
+        piece_length,
+        private: info.lenient_integer(PRIVATE_KEY) == Some(1),
+        v1,
+        v2,
+    })
+}
+
+fn read_piece_layers(root: &Dict) -> ReadResult<HashMap<Sha256Hash, Vec<Sha256Hash>>> {
+    let layers = match root.optional(PIECE_LAYERS) {
+        Some(layers) => Dict::new(layers, root.path(PIECE_LAYERS))?,
+        None => return Ok(HashMap::new()),
+    };
+    layers
+        .value
+        .entries()
+        .map(|(key, hashes)| {
+            let pieces_root = to_hash(key, &layers.path)?;
+            let path = format!("{}[0x{}]", layers.path, to_hex(&pieces_root));
+            Ok((pieces_root, as_hashes(hashes, &path)?))
+        })
+        .collect()
+}
+
+/// Checks that every file spanning more than one piece has a piece layer
+/// with one hash per piece.
+fn validate_piece_layers(
+    info: &Info,
+    piece_layers: &HashMap<Sha256Hash, Vec<Sha256Hash>>,
+) -> ReadResult<()> {
+    let v2 = match &info.v2 {
+        Some(v2) => v2,
+        None => return Ok(()),
+    };
+    for (path, file) in v2.file_tree.files() {
+        if file.length <= info.piece_length {
+            continue;
+        }
+        let root = file
+            .pieces_root
+            .expect("non-empty files have a pieces root");
+        let layer = piece_layers
+            .get(&root)
+            .ok_or_else(|| ReadError::MissingPieceLayer(path.join("/")))?;
+        let expected = file.length.div_ceil(info.piece_length) as usize;
+        if layer.len() != expected {
+            return Err(ReadError::InvalidPieceLayer {
+                path: format!("['{}'][0x{}]", PIECE_LAYERS, to_hex(&root)),
+                expected,
+                found: layer.len(),
+            });
         }
     }
+    Ok(())
+}
+
+fn read_meta_info(value: &Value, raw_info: Option<&[u8]>) -> ReadResult<MetaInfo> {
+    let root = Dict::new(value, String::new())?;
+    let info_dict = root.dict(INFO_KEY)?;
+    let info = read_info(&info_dict)?;
+    let raw_info = match raw_info {
+        Some(raw_info) => raw_info.to_vec(),
+        None => info_dict.value.to_bytes(),
+    };
+    let piece_layers = read_piece_layers(&root)?;
+    validate_piece_layers(&info, &piece_layers)?;
+
+    Ok(MetaInfo {
+        announce: root.lenient_string(ANNOUNCE_KEY),
+        announce_list: parse_announce_list(root.optional(ANNOUNCE_LIST_KEY)),
+        info,
+        piece_layers,
+        creation_date: root.lenient_integer(CREATION_DATE_KEY),
+        comment: root.lossy_string(COMMENT_KEY),
+        created_by: root.lossy_string(CREATED_BY_KEY),
+        encoding: root.lossy_string(ENCODING_KEY),
+        raw_info,
+    })
+}
+
+/// Reads metainfo from an already parsed value.
+///
+/// The original bytes of `info` are unknown here, so `raw_info` holds its
+/// canonical encoding, which only yields the right infohash if the source
+/// was canonical. Prefer [`from_bytes`] when the bytes are available.
+pub fn from_value(value: &Value) -> ReadResult<MetaInfo> {
+    read_meta_info(value, None)
+}
+
+pub fn from_bytes(bytes: &[u8]) -> ReadResult<MetaInfo> {
+    let value = try_parse_value(bytes.iter().copied())?;
+    let raw_info = find_span(bytes, &[INFO_KEY.into()])?.map(|span| &bytes[span]);
+    read_meta_info(&value, raw_info)
 }
 
-// TODO: Improve error handling
-pub fn read(
-    r: &mut impl std::io::Read,
-) -> Result<super::MetaInfo, crate::bencoding::parse::ParseError> {
-    let source = r.bytes().take_while(|x| x.is_ok()).map(|x| x.unwrap());
-    let value = try_parse_value(source)?;
+pub fn read(r: &mut impl Read) -> ReadResult<MetaInfo> {
+    let mut bytes = Vec::new();
+    r.read_to_end(&mut bytes)?;
+    from_bytes(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SINGLE: &[u8] = b"d8:announce14:http://tracker7:comment5:hello10:created by4:torr13:creation datei1608033138e8:encoding5:UTF-84:infod6:lengthi36e4:name5:a.txt12:piece lengthi32e6:pieces40:aaaaaaaaaaaaaaaaaaaabbbbbbbbbbbbbbbbbbbb7:privatei1eee";
+    const MULTIPLE: &[u8] = b"d4:infod5:filesld6:lengthi3e4:pathl3:dir5:b.txteed6:lengthi4e4:pathl5:c.txteee4:name4:root12:piece lengthi16e6:pieces20:aaaaaaaaaaaaaaaaaaaaee";
+
+    #[test]
+    fn test_single_file() {
+        let meta = from_bytes(SINGLE).unwrap();
+        assert_eq!(meta.announce.as_deref(), Some("http://tracker"));
+        assert_eq!(meta.comment.as_deref(), Some("hello"));
+        assert_eq!(meta.created_by.as_deref(), Some("torr"));
+        assert_eq!(meta.creation_date, Some(1608033138));
+        assert_eq!(meta.encoding.as_deref(), Some("UTF-8"));
+        assert_eq!(meta.info.name, "a.txt");
+        assert_eq!(meta.info.piece_length, 32);
+        let v1 = meta.info.v1.as_ref().unwrap();
+        assert_eq!(v1.pieces, vec![[b'a'; 20], [b'b'; 20]]);
+        assert_eq!(v1.files, Files::Single { length: 36 });
+        assert_eq!(meta.info.v2, None);
+        assert!(meta.info.private);
+        assert_eq!(meta.info.total_length(), 36);
+    }
 
-    recursive_print(&value, "");
+    #[test]
+    fn test_info_hash() {
+        // Non-canonical: unsorted keys and a leading zero must be hashed as is.
+        let source = b"d4:infod6:lengthi00e4:name1:a6:pieces0:12:piece lengthi1eee";
+        let meta = from_bytes(source).unwrap();
+        assert_eq!(meta.raw_info, &source[7..source.len() - 1]);
+        assert_eq!(
+            to_hex(&meta.info_hash_v1().unwrap()),
+            "2e1b91b08ec97b04509c1971d77b035ee8c45682"
+        );
+        assert_eq!(meta.info_hash_v2(), None);
+        assert_ne!(
+            from_value(&try_parse_value(source.iter().copied()).unwrap())
+                .unwrap()
+                .info_hash_v1(),
+            meta.info_hash_v1()
+        );
+    }
 
-    todo!("finish reading of metainfo");
+    #[test]
+    fn test_duplicate_info() {
+        // Parsing keeps the last `info`, so the infohash must be over it too.
+        let source = b"d4:infod6:lengthi0e4:name1:a12:piece lengthi1e6:pieces0:e\
+                       4:infod6:lengthi0e4:name1:b12:piece lengthi1e6:pieces0:ee";
+        let meta = from_bytes(source).unwrap();
+        assert_eq!(meta.info.name, "b");
+        assert_eq!(
+            meta.raw_info,
+            b"d6:lengthi0e4:name1:b12:piece lengthi1e6:pieces0:e"
+        );
+    }
+
+    #[test]
+    fn test_multiple_files() {
+        let meta = from_bytes(MULTIPLE).unwrap();
+        assert_eq!(meta.announce, None);
+        assert!(!meta.info.private);
+        assert_eq!(
+            meta.info.v1.as_ref().unwrap().files,
+            Files::Multiple(vec![
+                File {
+                    length: 3,
+                    path: vec!["dir".to_owned(), "b.txt".to_owned()],
+                    attr: None,
+                },
+                File {
+                    length: 4,
+                    path: vec!["c.txt".to_owned()],
+                    attr: None,
+                },
+            ])
+        );
+        assert_eq!(meta.info.total_length(), 7);
+    }
+
+    fn string(s: &str) -> Value {
+        Value::String(s.as_bytes().to_vec())
+    }
+
+    fn dict(entries: Vec<(&str, Value)>) -> Value {
+        Value::Dictionary(entries.into_iter().collect())
+    }
+
+    fn tree_file(length: i64, root: Option<u8>) -> Value {
+        let mut file = vec![("length", Value::Integer(length))];
+        if let Some(root) = root {
+            file.push(("pieces root", Value::String(vec![root; 32])));
+        }
+        dict(vec![("", dict(file))])
+    }
+
+    fn v2_torrent(file_tree: Value, piece_layers: Vec<(Vec<u8>, Vec<u8>)>) -> Vec<u8> {
+        dict(vec![
+            (
+                "info",
+                dict(vec![
+                    ("file tree", file_tree),
+                    ("meta version", Value::Integer(2)),
+                    ("name", string("root")),
+                    ("piece length", Value::Integer(16384)),
+                ]),
+            ),
+            (
+                "piece layers",
+                Value::Dictionary(
+                    piece_layers
+                        .into_iter()
+                        .map(|(k, v)| (k, Value::String(v)))
+                        .collect(),
+                ),
+            ),
+        ])
+        .to_bytes()
+    }
+
+    #[test]
+    fn test_v2_file_tree() {
+        let tree = dict(vec![
+            (
+                "dir",
+                dict(vec![
+                    ("big.bin", tree_file(40000, Some(1))),
+                    ("empty", tree_file(0, None)),
+                ]),
+            ),
+            ("small.txt", tree_file(10, Some(2))),
+        ]);
+        let meta = from_bytes(&v2_torrent(tree, vec![(vec![1; 32], vec![7; 96])])).unwrap();
+
+        assert_eq!(meta.info.v1, None);
+        assert_eq!(meta.info_hash_v1(), None);
+        let hash = meta.info_hash_v2().unwrap();
+        assert_eq!(meta.info_hash_v2_truncated().unwrap(), hash[..20]);
+        let v2 = meta.info.v2.as_ref().unwrap();
+        let files = v2.file_tree.files();
+        let paths = files.iter().map(|(p, _)| p.join("/")).collect::<Vec<_>>();
+        assert_eq!(paths, vec!["dir/big.bin", "dir/empty", "small.txt"]);
+        assert_eq!(files[0].1.pieces_root, Some([1; 32]));
+        assert_eq!(files[1].1.pieces_root, None);
+        assert_eq!(meta.info.total_length(), 40010);
+        assert_eq!(meta.piece_layers[&[1; 32]], vec![[7; 32]; 3]);
+    }
+
+    #[test]
+    fn test_v2_errors() {
+        let tree = dict(vec![("big.bin", tree_file(40000, Some(1)))]);
+        assert!(matches!(
+            from_bytes(&v2_torrent(tree.clone(), vec![])),
+            Err(ReadError::MissingPieceLayer(path)) if path == "big.bin"
+        ));
+        assert!(matches!(
+            from_bytes(&v2_torrent(tree.clone(), vec![(vec![1; 32], vec![7; 64])])),
+            Err(ReadError::InvalidPieceLayer {
+                expected: 3,
+                found: 2,
+                ..
+            })
+        ));
+        assert!(matches!(
+            from_bytes(&v2_torrent(tree, vec![(vec![1; 32], vec![7; 65])])),
+            Err(ReadError::InvalidHashLength { length: 65, .. })
+        ));
+        assert!(matches!(
+            from_bytes(&v2_torrent(tree_file(1, Some(1)), vec![])),
+            Err(ReadError::InvalidFileTree(path)) if path == "['info']['file tree']"
+        ));
+        assert!(matches!(
+            from_bytes(&v2_torrent(dict(vec![("a", tree_file(1, None))]), vec![])),
+            Err(ReadError::MissingKey(path)) if path == "['info']['file tree']['a']['']['pieces root']"
+        ));
+        assert!(matches!(
+            from_bytes(b"d4:infod9:file treed1:ad0:d6:lengthi0eeee12:meta versioni3e4:name1:a12:piece lengthi16384eee"),
+            Err(ReadError::UnsupportedMetaVersion(3))
+        ));
+        assert!(matches!(
+            from_bytes(b"d4:infod9:file treed1:ad0:d6:lengthi0eeee12:meta versioni2e4:name1:a12:piece lengthi1000eee"),
+            Err(ReadError::InvalidPieceLength(1000))
+        ));
+        let huge = || tree_file(i64::MAX, Some(1));
+        let tree = dict(vec![("a", huge()), ("b", huge()), ("c", huge())]);
+        assert!(matches!(
+            from_bytes(&v2_torrent(tree, vec![])),
+            Err(ReadError::LengthOverflow)
+        ));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(
+            from_bytes(b"d8:announce3:urle"),
+            Err(ReadError::MissingKey(path)) if path == "['info']"
+        ));
+        assert!(matches!(
+            from_bytes(b"d4:infod4:name1:a12:piece lengthi-1e6:pieces0:6:lengthi0eee"),
+            Err(ReadError::NegativeInteger(path)) if path == "['info']['piece length']"
+        ));
+        assert!(matches!(
+            from_bytes(b"d4:infod4:name1:a12:piece lengthi1e6:pieces0:6:lengthi9223372036854775808eee"),
+            Err(ReadError::IntegerOutOfRange(path)) if path == "['info']['length']"
+        ));
+        assert!(matches!(
+            from_bytes(b"d4:infod4:name1:a12:piece lengthi1e6:pieces3:abc6:lengthi0eee"),
+            Err(ReadError::InvalidPiecesLength(3))
+        ));
+        assert!(matches!(
+            from_bytes(b"d4:infod4:name1:a12:piece lengthi1e6:pieces0:ee"),
+            Err(ReadError::MissingFiles)
+        ));
+        assert!(matches!(
+            from_bytes(b"d4:infod5:filesle6:lengthi1e4:name1:a12:piece lengthi1e6:pieces0:ee"),
+            Err(ReadError::AmbiguousFiles)
+        ));
+        assert!(matches!(
+            from_bytes(b"d4:infod5:filesld6:lengthi1e4:pathleee4:name1:a12:piece lengthi1e6:pieces0:ee"),
+            Err(ReadError::EmptyPath(path)) if path == "['info']['files'][0]['path']"
+        ));
+        assert!(matches!(
+            from_bytes(b"d4:infod5:filesld6:lengthi1e4:pathli5eeee4:name1:a12:piece lengthi1e6:pieces0:ee"),
+            Err(ReadError::UnexpectedType { path, expected: ValueType::String, found: ValueType::Integer })
+                if path == "['info']['files'][0]['path'][0]"
+        ));
+        assert!(matches!(
+            from_bytes(b"d4:infod6:lengthi0e4:name1:a12:piece lengthi0e6:pieces0:ee"),
+            Err(ReadError::InvalidPieceLength(0))
+        ));
+        assert!(matches!(
+            from_bytes(b"d4:infod6:lengthi1125899906842624e4:name1:a12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaaee"),
+            Err(ReadError::InvalidPieceCount { expected: 68719476736, found: 1 })
+        ));
+        let file = "d6:lengthi9223372036854775807e4:pathl1:aee";
+        let source = format!(
+            "d4:infod5:filesl{}{}{}e4:name1:a12:piece lengthi1e6:pieces0:ee",
+            file, file, file
+        );
+        assert!(matches!(
+            from_bytes(source.as_bytes()),
+            Err(ReadError::LengthOverflow)
+        ));
+    }
+
+    #[test]
+    fn test_lenient_optional_fields() {
+        // A GBK comment, and optional fields of the wrong type.
+        let source = b"d8:announcei1e7:comment4:\xb2\xe2\xca\xd410:created byle13:creation date3:now8:encoding3:GBK\
+                       4:infod5:filesld4:attri1e6:lengthi0e4:pathl1:aeee4:name1:a12:piece lengthi1e6:pieces0:7:private3:yesee";
+        let meta = from_bytes(source).unwrap();
+        assert_eq!(meta.announce, None);
+        assert_eq!(
+            meta.comment.as_deref(),
+            Some("\u{fffd}\u{fffd}\u{fffd}\u{fffd}")
+        );
+        assert_eq!(meta.created_by, None);
+        assert_eq!(meta.creation_date, None);
+        assert_eq!(meta.encoding.as_deref(), Some("GBK"));
+        assert!(!meta.info.private);
+        match &meta.info.v1.as_ref().unwrap().files {
+            Files::Multiple(files) => assert_eq!(files[0].attr, None),
+            files => panic!("unexpected {:?}", files),
+        }
+        assert!(from_bytes(
+            b"d8:announce2:\xff\xfe4:infod6:lengthi0e4:name1:a12:piece lengthi1e6:pieces0:ee"
+        )
+        .unwrap()
+        .announce
+        .is_none());
+    }
+
+    #[test]
+    fn test_announce_list() {
+        let meta = from_bytes(b"d8:announce1:a13:announce-listll1:bel1:c1:dee4:infod6:lengthi0e4:name1:a12:piece lengthi1e6:pieces0:ee").unwrap();
+        assert_eq!(meta.announce.as_deref(), Some("a"));
+        assert_eq!(meta.announce_list, [vec!["b"], vec!["c", "d"]]);
+        assert_eq!(
+            from_bytes(SINGLE).unwrap().announce_list,
+            Vec::<Vec<String>>::new()
+        );
+    }
 }
@@ -0,0 +1,68 @@
+//! Files on disk for the tests of torrent creation and verification.
+
+use std::path::{Path, PathBuf};
+
+/// A fresh directory below the system temp dir, removed when dropped so
+/// that failing tests do not leave it behind.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// `name` must be unique among the tests.
+    pub fn new(name: &str) -> TempDir {
+        let path = std::env::temp_dir().join(format!("torr-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.0.join(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Writes `files` below `dir`, the `ind`th holding the bytes `i * 7 + ind`,
+/// and returns their contents.
+pub fn write_files(dir: &Path, files: &[(&str, usize)]) -> Vec<Vec<u8>> {
+    let mut contents = Vec::new();
+    for (ind, (path, length)) in files.iter().enumerate() {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let data = (0..*length)
+            .map(|i| (i * 7 + ind) as u8)
+            .collect::<Vec<_>>();
+        std::fs::write(path, &data).unwrap();
+        contents.push(data);
+    }
+    contents
+}
+
+/// `testdata/v2.torrent`, written by `testdata/make_v2_torrent.py` from the
+/// BEP 52 text rather than with this crate.
+pub const V2_TORRENT: &[u8] = include_bytes!("../../testdata/v2.torrent");
+
+/// The files of [`V2_TORRENT`] and their contents.
+pub fn v2_torrent_files() -> Vec<(&'static str, Vec<u8>)> {
+    vec![
+        ("a", (0..5 * 16384 + 100).map(|i| (i % 251) as u8).collect()),
+        ("dir/b", (0..20000).map(|i| (i % 13) as u8).collect()),
+    ]
+}
+
+/// Writes the files of [`V2_TORRENT`] below `dir`.
+pub fn write_v2_torrent_files(dir: &Path) {
+    for (path, data) in v2_torrent_files() {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, data).unwrap();
+    }
+}
@@ -0,0 +1,209 @@
+//! Tracker selection for multi-tracker torrents (BEP 12).
+
+use std::hash::{BuildHasher, RandomState};
+
+use super::MetaInfo;
+use crate::bencoding::value::Value;
+
+/// Tiers of tracker URLs in the order they should be tried: tiers one after
+/// another, and within a tier from the front. A tracker that answers is
+/// moved to the front of its tier so that it is tried first next time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrackerList {
+    tiers: Vec<Vec<String>>,
+}
+
+impl TrackerList {
+    /// Keeps the order of `tiers`, dropping empty ones.
+    pub fn new(tiers: Vec<Vec<String>>) -> TrackerList {
+        TrackerList {
+            tiers: tiers.into_iter().filter(|tier| !tier.is_empty()).collect(),
+        }
+    }
+
+    /// The trackers of `announce-list`, or a single tier with `announce`
+    /// when the list is absent or has no URLs. As BEP 12 asks, `announce`
+    /// is ignored otherwise. Tiers are not shuffled yet.
+    pub fn from_meta(meta: &MetaInfo) -> TrackerList {
+        let list = TrackerList::new(meta.announce_list.clone());
+        if !list.is_empty() {
+            return list;
+        }
+        TrackerList::new(meta.announce.iter().map(|url| vec![url.clone()]).collect())
+    }
+
+    pub fn tiers(&self) -> &[Vec<String>] {
+        &self.tiers
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiers.is_empty()
+    }
+
+    /// Number of trackers in all tiers.
+    pub fn len(&self) -> usize {
+        self.tiers.iter().map(Vec::len).sum()
+    }
+
+    /// Every tracker with its tier, in the order to try them.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.tiers
+            .iter()
+            .enumerate()
+            .flat_map(|(ind, tier)| tier.iter().map(move |url| (ind, url.as_str())))
+    }
+
+    /// Shuffles every tier, which clients do once when they load a torrent
+    /// so that the load spreads over the trackers of a tier.
+    pub fn shuffle(&mut self) {
+        let state = RandomState::new();
+        let mut counter = 0u64;
+        self.shuffle_with(|n| {
+            counter += 1;
+            (state.hash_one(counter) % n as u64) as usize
+        });
+    }
+
+    /// Shuffles every tier with `random(n)`, which must return a number
+    /// below `n`.
+    pub fn shuffle_with(&mut self, mut random: impl FnMut(usize) -> usize) {
+        for tier in &mut self.tiers {
+            for i in (1..tier.len()).rev() {
+                tier.swap(i, random(i + 1));
+            }
+        }
+    }
+
+    /// Moves `url` to the front of its tier after it answered an announce.
+    /// Returns false when the URL is not in the list.
+    pub fn promote(&mut self, url: &str) -> bool {
+        for tier in &mut self.tiers {
+            if let Some(ind) = tier.iter().position(|u| u == url) {
+                tier[..=ind].rotate_right(1);
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// The tiers of an `announce-list` value. Tiers that are not lists and URLs
+/// that are not UTF-8 strings are skipped rather than failing the torrent,
+/// so that a broken list falls back to `announce`.
+pub(super) fn parse_announce_list(value: Option<&Value>) -> Vec<Vec<String>> {
+    let tiers = match value {
+        Some(Value::List(tiers)) => tiers,
+        _ => return Vec::new(),
+    };
+    tiers
+        .iter()
+        .filter_map(|tier| match tier {
+            Value::List(urls) => Some(
+                urls.iter()
+                    .filter_map(|url| match url {
+                        Value::String(bytes) => String::from_utf8(bytes.clone()).ok(),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bencode;
+    use crate::metainfo::read::from_bytes;
+
+    fn meta(announce: Option<&str>, announce_list: &[&[&str]]) -> MetaInfo {
+        let announce_list = announce_list
+            .iter()
+            .map(|tier| tier.to_vec())
+            .collect::<Vec<_>>();
+        let source = bencode! {
+            "announce" => announce,
+            "announce-list" => (!announce_list.is_empty()).then_some(announce_list),
+            "info" => { "length" => 1, "name" => "a", "piece length" => 1, "pieces" => &[0u8; 20] },
+        };
+        from_bytes(&source.to_bytes()).unwrap()
+    }
+
+    fn urls(list: &TrackerList) -> Vec<&str> {
+        list.iter().map(|(_, url)| url).collect()
+    }
+
+    #[test]
+    fn test_from_meta() {
+        let list = TrackerList::from_meta(&meta(Some("a"), &[&["b", "c"], &[], &["d"]]));
+        assert_eq!(list.tiers().len(), 2);
+        assert_eq!(list.len(), 3);
+        assert_eq!(
+            list.iter().collect::<Vec<_>>(),
+            [(0, "b"), (0, "c"), (1, "d")]
+        );
+
+        let list = TrackerList::from_meta(&meta(Some("a"), &[&[]]));
+        assert_eq!(urls(&list), ["a"]);
+        let list = TrackerList::from_meta(&meta(Some("a"), &[]));
+        assert_eq!(urls(&list), ["a"]);
+        assert!(TrackerList::from_meta(&meta(None, &[])).is_empty());
+    }
+
+    #[test]
+    fn test_malformed_announce_list() {
+        let info = "4:infod6:lengthi1e4:name1:a12:piece lengthi1e6:pieces20:01234567890123456789e";
+        let read = |announce_list: &[u8]| {
+            let mut source = b"d8:announce1:a13:announce-list".to_vec();
+            source.extend_from_slice(announce_list);
+            source.extend_from_slice(info.as_bytes());
+            source.push(b'e');
+            from_bytes(&source).unwrap()
+        };
+
+        for announce_list in [&b"l1:ae"[..], b"1:b", b"i1e", b"ll2:\xff\xfeee", b"lli1eee"] {
+            let meta = read(announce_list);
+            assert!(meta.announce_list.iter().all(Vec::is_empty));
+            assert_eq!(urls(&TrackerList::from_meta(&meta)), ["a"]);
+        }
+        let meta = read(b"l1:bl1:ci2e1:\xffel1:dee");
+        assert_eq!(meta.announce_list, [vec!["c"], vec!["d"]]);
+        assert_eq!(urls(&TrackerList::from_meta(&meta)), ["c", "d"]);
+    }
+
+    #[test]
+    fn test_promote() {
+        let mut list = TrackerList::new(vec![
+            vec!["a".into(), "b".into(), "c".into()],
+            vec!["d".into(), "e".into()],
+        ]);
+        assert!(list.promote("c"));
+        assert!(list.promote("e"));
+        assert!(!list.promote("x"));
+        assert_eq!(urls(&list), ["c", "a", "b", "e", "d"]);
+        assert!(list.promote("c"));
+        assert_eq!(urls(&list), ["c", "a", "b", "e", "d"]);
+    }
+
+    #[test]
+    fn test_shuffle() {
+        let tiers = vec![
+            vec!["a".into(), "b".into(), "c".into()],
+            vec!["d".into(), "e".into()],
+        ];
+        let mut list = TrackerList::new(tiers.clone());
+        // Always picking the first remaining element rotates each tier.
+        list.shuffle_with(|_| 0);
+        assert_eq!(urls(&list), ["b", "c", "a", "e", "d"]);
+
+        let mut list = TrackerList::new(tiers);
+        list.shuffle();
+        assert_eq!(list.tiers().len(), 2);
+        for tier in list.tiers() {
+            let mut sorted = tier.clone();
+            sorted.sort();
+            assert!(sorted == ["a", "b", "c"] || sorted == ["d", "e"]);
+        }
+    }
+}
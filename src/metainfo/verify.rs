@@ -0,0 +1,524 @@
+//! Checking data on disk against a torrent.
+
+use std::fmt;
+use std::path::Path;
+
+use super::hashing::{hash_pieces, plan_v1, plan_v2, thread_count, Source};
+use super::hybrid::HybridError;
+use super::{Files, MetaInfo, Sha256Hash};
+
+/// One bit per piece, the first piece in the high bit of the first byte as
+/// in the peer wire protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitfield {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl Bitfield {
+    /// A bitfield of `len` unset bits.
+    pub fn new(len: usize) -> Bitfield {
+        Bitfield {
+            bytes: vec![0; len.div_ceil(8)],
+            len,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, index: usize) -> bool {
+        index < self.len && self.bytes[index / 8] & (0x80 >> (index % 8)) != 0
+    }
+
+    pub fn set(&mut self, index: usize) {
+        assert!(index < self.len, "bit {} out of {}", index, self.len);
+        self.bytes[index / 8] |= 0x80 >> (index % 8);
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.bytes.iter().map(|b| b.count_ones() as usize).sum()
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileReport {
+    /// Path components relative to the torrent directory.
+    pub path: Vec<String>,
+    pub length: u64,
+    /// Pieces holding data of the file, including pieces shared with
+    /// neighbouring files.
+    pub pieces: usize,
+    pub valid_pieces: usize,
+    /// The file does not exist.
+    pub missing: bool,
+}
+
+impl FileReport {
+    pub fn is_complete(&self) -> bool {
+        !self.missing && self.valid_pieces == self.pieces
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// Set for every piece that matches its hashes.
+    pub pieces: Bitfield,
+    /// Every file but padding files, in torrent order.
+    pub files: Vec<FileReport>,
+}
+
+impl Report {
+    pub fn is_complete(&self) -> bool {
+        self.pieces.count_ones() == self.pieces.len() && self.files.iter().all(|f| f.is_complete())
+    }
+}
+
+/// The largest piece length [`verify`] accepts. Every piece is hashed in
+/// full, padding included, so this bounds the work a single hash from the
+/// torrent can cause.
+pub const MAX_PIECE_LENGTH: u64 = 1 << 30;
+
+#[derive(Debug)]
+pub enum VerifyError {
+    Hybrid(HybridError),
+    /// The piece length is 0 or above [`MAX_PIECE_LENGTH`].
+    InvalidPieceLength(u64),
+    /// The torrent does not have one hash per piece of its files.
+    PieceCountMismatch {
+        expected: u64,
+        found: u64,
+    },
+    /// The file lengths do not fit in a `u64`.
+    LengthOverflow,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VerifyError::Hybrid(e) => e.fmt(f),
+            VerifyError::InvalidPieceLength(length) => write!(
+                f,
+                "invalid piece length {}, must be between 1 and {}",
+                length, MAX_PIECE_LENGTH
+            ),
+            VerifyError::PieceCountMismatch { expected, found } => write!(
+                f,
+                "the files make up {} pieces but the torrent has {} hashes",
+                expected, found
+            ),
+            VerifyError::LengthOverflow => write!(f, "the total length of the files overflows"),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+impl From<HybridError> for VerifyError {
+    fn from(e: HybridError) -> Self {
+        VerifyError::Hybrid(e)
+    }
+}
+
+fn checked_sum(mut counts: impl Iterator<Item = u64>) -> Result<u64, VerifyError> {
+    counts
+        .try_fold(0u64, u64::checked_add)
+        .ok_or(VerifyError::LengthOverflow)
+}
+
+/// Fails unless the torrent has a hash for every piece of `sources`, so
+/// that the pieces planned are bounded by the hashes and not by lengths.
+fn check_piece_count(
+    meta: &MetaInfo,
+    sources: &[Source],
+    roots: &[Option<Sha256Hash>],
+) -> Result<(), VerifyError> {
+    let info = &meta.info;
+    let piece_length = info.piece_length;
+    let check = |expected, found| {
+        if expected == found {
+            Ok(())
+        } else {
+            Err(VerifyError::PieceCountMismatch { expected, found })
+        }
+    };
+    let expected = match &info.v2 {
+        Some(_) => checked_sum(
+            sources
+                .iter()
+                .map(|source| source.length.div_ceil(piece_length)),
+        )?,
+        None => checked_sum(sources.iter().map(|source| source.length))?.div_ceil(piece_length),
+    };
+    if let Some(v1) = &info.v1 {
+        check(expected, v1.pieces.len() as u64)?;
+    }
+    if info.v2.is_some() {
+        let found = checked_sum(sources.iter().zip(roots).map(|(source, root)| {
+            match (source.length, root) {
+                (0, _) | (_, None) => 0,
+                (length, Some(_)) if length <= piece_length => 1,
+                (_, Some(root)) => meta.piece_layers.get(root).map_or(0, |l| l.len() as u64),
+            }
+        }))?;
+        check(expected, found)?;
+    }
+    Ok(())
+}
+
+/// The torrent's files as found below `dir`, with the `pieces root` of each
+/// for v2 torrents. Hybrid torrents are read as v2, their padding being
+/// added by [`plan_v2`].
+fn sources(meta: &MetaInfo, dir: &Path) -> (Vec<Source>, Vec<Option<Sha256Hash>>) {
+    let info = &meta.info;
+    let content_dir = dir.join(&info.name);
+    let source = |components: Vec<String>, length| Source {
+        path: Some(
+            components
+                .iter()
+                .fold(content_dir.clone(), |p, c| p.join(c)),
+        ),
+        components,
+        length,
+    };
+    let single = |length| Source {
+        path: Some(dir.join(&info.name)),
+        components: vec![info.name.clone()],
+        length,
+    };
+
+    if let Some(v2) = &info.v2 {
+        let files = v2.file_tree.files();
+        let is_single =
+            matches!(&files[..], [(path, _)] if path.len() == 1 && path[0] == info.name);
+        files
+            .into_iter()
+            .map(|(path, file)| {
+                let source = if is_single {
+                    single(file.length)
+                } else {
+                    source(path, file.length)
+                };
+                (source, file.pieces_root)
+            })
+            .unzip()
+    } else if let Some(v1) = &info.v1 {
+        let sources = match &v1.files {
+            Files::Single { length } => vec![single(*length)],
+            Files::Multiple(files) => files
+                .iter()
+                .map(|file| Source {
+                    path: (!file.is_padding())
+                        .then(|| file.path.iter().fold(content_dir.clone(), |p, c| p.join(c))),
+                    components: file.path.clone(),
+                    length: file.length,
+                })
+                .collect(),
+        };
+        let roots = vec![None; sources.len()];
+        (sources, roots)
+    } else {
+        (Vec::new(), Vec::new())
+    }
+}
+
+/// Hashes the data of `meta` found in `dir` on `threads` threads (all cores
+/// when 0). A single-file torrent is expected at `dir/<name>`, the files of
+/// a multi-file torrent below the directory `dir/<name>`.
+///
+/// A piece is valid when it matches every hash the torrent has for it: its
+/// v1 SHA-1 and, for v2, the piece layer entry or, for files of at most one
+/// piece, the `pieces root`. Unreadable and short files only invalidate the
+/// pieces they are part of.
+///
+/// Fails for hybrid torrents whose v1 and v2 metadata disagree, since they
+/// do not describe a single piece layout, for piece lengths above
+/// [`MAX_PIECE_LENGTH`] and for torrents lacking a hash for some piece.
+pub fn verify(
+    meta: &MetaInfo,
+    dir: impl AsRef<Path>,
+    threads: usize,
+) -> Result<Report, VerifyError> {
+    let info = &meta.info;
+    if info.piece_length == 0 || info.piece_length > MAX_PIECE_LENGTH {
+        return Err(VerifyError::InvalidPieceLength(info.piece_length));
+    }
+    if info.is_hybrid() {
+        meta.validate_hybrid()?;
+    }
+    let (sources, roots) = sources(meta, dir.as_ref());
+    check_piece_count(meta, &sources, &roots)?;
+    let pieces = match (&info.v1, &info.v2) {
+        (_, None) => plan_v1(&sources, info.piece_length),
+        (v1, Some(_)) => plan_v2(&sources, info.piece_length, v1.is_some()),
+    };
+    let hashes = hash_pieces(&sources, &pieces, thread_count(threads));
+
+    let mut bitfield = Bitfield::new(pieces.len());
+    let mut files = sources
+        .iter()
+        .map(|source| FileReport {
+            path: source.components.clone(),
+            length: source.length,
+            pieces: 0,
+            valid_pieces: 0,
+            missing: source.path.as_deref().is_some_and(|p| !p.exists()),
+        })
+        .collect::<Vec<_>>();
+    let mut v1_pieces = info.v1.iter().flat_map(|v1| &v1.pieces);
+    for (ind, (piece, result)) in pieces.iter().zip(hashes).enumerate() {
+        let expected_v1 = if piece.v1 { v1_pieces.next() } else { None };
+        let expected_v2 = piece.v2.and_then(|(source, _)| {
+            let root = roots[source].as_ref()?;
+            if sources[source].length <= info.piece_length {
+                return Some(root);
+            }
+            let index = piece.segments[0].offset / info.piece_length;
+            meta.piece_layers.get(root)?.get(index as usize)
+        });
+        let valid = result.is_ok_and(|hashes| {
+            (!piece.v1 || hashes.v1.as_ref() == expected_v1)
+                && (piece.v2.is_none() || hashes.v2.as_ref() == expected_v2)
+        });
+        if valid {
+            bitfield.set(ind);
+        }
+        for segment in &piece.segments {
+            let file = &mut files[segment.source];
+            file.pieces += 1;
+            file.valid_pieces += valid as usize;
+        }
+    }
+
+    let files = sources
+        .iter()
+        .zip(files)
+        .filter(|(source, _)| source.path.is_some())
+        .map(|(_, file)| file)
+        .collect();
+    Ok(Report {
+        pieces: bitfield,
+        files,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::bencode;
+    use crate::metainfo::create::{create, CreateOptions, Version};
+    use crate::metainfo::read::from_bytes;
+    use crate::metainfo::test_util::{write_files, write_v2_torrent_files, TempDir, V2_TORRENT};
+
+    const PIECE: u64 = 16 * 1024;
+
+    /// Creates `files` below `<dir>/data` and a torrent of them.
+    fn fixture(name: &str, files: &[(&str, usize)], version: Version) -> (TempDir, MetaInfo) {
+        let dir = TempDir::new(&format!("verify-{}", name));
+        write_files(&dir.join("data"), files);
+        let options = CreateOptions {
+            version,
+            piece_length: Some(PIECE),
+            threads: 2,
+            ..CreateOptions::default()
+        };
+        let meta = create(dir.join("data"), &options).unwrap();
+        (dir, meta)
+    }
+
+    fn corrupt(path: PathBuf, offset: u64) {
+        let mut data = std::fs::read(&path).unwrap();
+        data[offset as usize] ^= 1;
+        std::fs::write(path, data).unwrap();
+    }
+
+    fn summary(report: &Report) -> Vec<(String, usize, usize, bool)> {
+        report
+            .files
+            .iter()
+            .map(|f| (f.path.join("/"), f.valid_pieces, f.pieces, f.missing))
+            .collect()
+    }
+
+    #[test]
+    fn test_bitfield() {
+        let mut bitfield = Bitfield::new(10);
+        bitfield.set(0);
+        bitfield.set(9);
+        assert_eq!(bitfield.as_bytes(), [0x80, 0x40]);
+        assert!(bitfield.get(9) && !bitfield.get(8) && !bitfield.get(10));
+        assert_eq!(bitfield.count_ones(), 2);
+        assert!(Bitfield::new(0).is_empty());
+    }
+
+    #[test]
+    fn test_hybrid() {
+        let files = [("b/large", 3 * PIECE as usize + 5), ("a.txt", 10)];
+        let (dir, meta) = fixture("hybrid", &files, Version::Hybrid);
+        let report = verify(&meta, dir.path(), 2).unwrap();
+        assert!(report.is_complete());
+        assert_eq!(report.pieces.len(), 5);
+
+        corrupt(dir.join("data/b/large"), PIECE + 1);
+        std::fs::remove_file(dir.join("data/a.txt")).unwrap();
+        let report = verify(&meta, dir.path(), 2).unwrap();
+        assert!(!report.is_complete());
+        assert_eq!(report.pieces.as_bytes(), [0b0101_1000]);
+        assert_eq!(
+            summary(&report),
+            [
+                ("a.txt".to_owned(), 0, 1, true),
+                ("b/large".to_owned(), 3, 4, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_v1() {
+        let files = [
+            ("a", PIECE as usize + 100),
+            ("b", 200),
+            ("c", PIECE as usize),
+        ];
+        let (dir, meta) = fixture("v1", &files, Version::V1);
+        assert!(verify(&meta, dir.path(), 0).unwrap().is_complete());
+
+        corrupt(dir.join("data/b"), 0);
+        let report = verify(&meta, dir.path(), 1).unwrap();
+        assert_eq!(report.pieces.as_bytes(), [0b1010_0000]);
+        assert_eq!(
+            summary(&report),
+            [
+                ("a".to_owned(), 1, 2, false),
+                ("b".to_owned(), 0, 1, false),
+                ("c".to_owned(), 1, 2, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_v2_single_file() {
+        let (dir, _) = fixture("v2", &[("file", 2 * PIECE as usize + 1)], Version::V2);
+        let file = dir.join("data/file");
+        let options = CreateOptions {
+            version: Version::V2,
+            piece_length: Some(PIECE),
+            ..CreateOptions::default()
+        };
+        let meta = create(&file, &options).unwrap();
+        let data_dir = dir.join("data");
+        assert!(verify(&meta, &data_dir, 1).unwrap().is_complete());
+
+        let data = std::fs::read(&file).unwrap();
+        std::fs::write(&file, &data[..PIECE as usize + 5]).unwrap();
+        let report = verify(&meta, &data_dir, 1).unwrap();
+        assert_eq!(report.pieces.as_bytes(), [0b1000_0000]);
+        assert_eq!(summary(&report), [("file".to_owned(), 1, 3, false)]);
+    }
+
+    #[test]
+    fn test_huge_pieces() {
+        // Neither the length nor the piece length may decide what is read
+        // or allocated before the file is checked.
+        let length = MAX_PIECE_LENGTH;
+        let v1 = bencode! {
+            "info" => {
+                "length" => 2 * length,
+                "name" => "huge",
+                "piece length" => length,
+                "pieces" => &[0u8; 40],
+            },
+        };
+        let v2 = bencode! {
+            "info" => {
+                "file tree" => { "huge" => { "" => { "length" => length, "pieces root" => &[1u8; 32] } } },
+                "meta version" => 2,
+                "name" => "huge",
+                "piece length" => length,
+            },
+        };
+        let dir = TempDir::new("verify-huge");
+        for (source, pieces) in [(v1, 2), (v2, 1)] {
+            let meta = from_bytes(&source.to_bytes()).unwrap();
+            let report = verify(&meta, dir.path(), 1).unwrap();
+            assert_eq!(report.pieces.len(), pieces);
+            assert_eq!(summary(&report), [("huge".to_owned(), 0, pieces, true)]);
+
+            std::fs::write(dir.join("huge"), b"abc").unwrap();
+            let report = verify(&meta, dir.path(), 1).unwrap();
+            assert_eq!(summary(&report), [("huge".to_owned(), 0, pieces, false)]);
+            std::fs::remove_file(dir.join("huge")).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_invalid_piece_layout() {
+        let dir = TempDir::new("verify-invalid");
+        let v1 = |length: u64, piece_length: u64| {
+            let mut meta = from_bytes(
+                &bencode! {
+                    "info" => {
+                        "length" => 0,
+                        "name" => "huge",
+                        "piece length" => 16384,
+                        "pieces" => "",
+                    },
+                }
+                .to_bytes(),
+            )
+            .unwrap();
+            meta.info.piece_length = piece_length;
+            let v1 = meta.info.v1.as_mut().unwrap();
+            v1.files = Files::Single { length };
+            v1.pieces = vec![[0; 20]];
+            meta
+        };
+
+        for piece_length in [0, 2 * MAX_PIECE_LENGTH] {
+            assert!(matches!(
+                verify(&v1(1, piece_length), dir.path(), 1),
+                Err(VerifyError::InvalidPieceLength(l)) if l == piece_length
+            ));
+        }
+        // A single hash must not plan 2^36 pieces.
+        assert!(matches!(
+            verify(&v1(1 << 50, 16384), dir.path(), 1),
+            Err(VerifyError::PieceCountMismatch {
+                expected: 68719476736,
+                found: 1
+            })
+        ));
+
+        // Piece layers that were cut short after reading.
+        let files = [("large", 3 * PIECE as usize)];
+        let (dir, mut meta) = fixture("short-layers", &files, Version::V2);
+        for layer in meta.piece_layers.values_mut() {
+            layer.truncate(1);
+        }
+        assert!(matches!(
+            verify(&meta, dir.path(), 1),
+            Err(VerifyError::PieceCountMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_v2_torrent() {
+        let meta = from_bytes(V2_TORRENT).unwrap();
+        let dir = TempDir::new("verify-v2-torrent");
+        write_v2_torrent_files(&dir.join(&meta.info.name));
+        let report = verify(&meta, dir.path(), 2).unwrap();
+        assert!(report.is_complete());
+        assert_eq!(report.pieces.len(), 4);
+    }
+}
@@ -0,0 +1,163 @@
+use super::keys::*;
+use super::{FileTree, Files, Info, MetaInfo};
+use crate::bencoding::dictionary::Dictionary;
+use crate::bencoding::value::Value;
+
+impl Info {
+    /// The canonical `info` dictionary.
+    pub fn to_value(&self) -> Value {
+        let mut info = Dictionary::new();
+        info.insert(NAME_KEY, self.name.as_str().into());
+        info.insert(PIECE_LENGTH_KEY, self.piece_length.into());
+        if self.private {
+            info.insert(PRIVATE_KEY, 1.into());
+        }
+        if let Some(v1) = &self.v1 {
+            info.insert(PIECES_KEY, v1.pieces.concat().into());
+            match &v1.files {
+                Files::Single { length } => {
+                    info.insert(LENGTH_KEY, (*length).into());
+                }
+                Files::Multiple(files) => {
+                    let files = files
+                        .iter()
+                        .map(|file| {
+                            let mut entry = Dictionary::new();
+                            entry.insert(LENGTH_KEY, file.length.into());
+                            entry.insert(PATH_KEY, file.path.clone().into());
+                            if let Some(attr) = &file.attr {
+                                entry.insert(ATTR_KEY, attr.as_str().into());
+                            }
+                            Value::Dictionary(entry)
+                        })
+                        .collect::<Vec<_>>();
+                    info.insert(FILES_KEY, files.into());
+                }
+            }
+        }
+        if let Some(v2) = &self.v2 {
+            info.insert(META_VERSION_KEY, 2.into());
+            info.insert(FILE_TREE_KEY, file_tree_to_value(&v2.file_tree));
+        }
+        Value::Dictionary(info)
+    }
+}
+
+fn file_tree_to_value(tree: &FileTree) -> Value {
+    match tree {
+        FileTree::File(file) => {
+            let mut leaf = Dictionary::new();
+            leaf.insert(LENGTH_KEY, file.length.into());
+            if let Some(root) = &file.pieces_root {
+                leaf.insert(PIECES_ROOT_KEY, root.into());
+            }
+            let mut node = Dictionary::new();
+            node.insert("", Value::Dictionary(leaf));
+            Value::Dictionary(node)
+        }
+        FileTree::Directory(entries) => Value::Dictionary(
+            entries
+                .iter()
+                .map(|(name, tree)| (name.as_str(), file_tree_to_value(tree)))
+                .collect(),
+        ),
+    }
+}
+
+impl MetaInfo {
+    /// Encodes the torrent file. `info` is written as `raw_info` so the
+    /// infohash does not change; everything else is canonical.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut root = Dictionary::new();
+        if let Some(announce) = &self.announce {
+            root.insert(ANNOUNCE_KEY, announce.as_str().into());
+        }
+        if !self.announce_list.is_empty() {
+            root.insert(ANNOUNCE_LIST_KEY, self.announce_list.clone().into());
+        }
+        if let Some(comment) = &self.comment {
+            root.insert(COMMENT_KEY, comment.as_str().into());
+        }
+        if let Some(created_by) = &self.created_by {
+            root.insert(CREATED_BY_KEY, created_by.as_str().into());
+        }
+        if let Some(creation_date) = self.creation_date {
+            root.insert(CREATION_DATE_KEY, creation_date.into());
+        }
+        if let Some(encoding) = &self.encoding {
+            root.insert(ENCODING_KEY, encoding.as_str().into());
+        }
+        if !self.piece_layers.is_empty() {
+            let layers = self
+                .piece_layers
+                .iter()
+                .map(|(root, hashes)| (root.to_vec(), hashes.concat().into()))
+                .collect::<Dictionary>();
+            root.insert(PIECE_LAYERS, Value::Dictionary(layers));
+        }
+
+        let mut res = vec![b'd'];
+        let mut write_entry = |key: &[u8], value: &[u8]| {
+            res.extend(Value::from(key).to_bytes());
+            res.extend(value);
+        };
+        let mut entries = root.iter().peekable();
+        while let Some((key, value)) = entries.next_if(|(key, _)| *key < INFO_KEY.as_bytes()) {
+            write_entry(key, &value.to_bytes());
+        }
+        write_entry(INFO_KEY.as_bytes(), &self.raw_info);
+        for (key, value) in entries {
+            write_entry(key, &value.to_bytes());
+        }
+        res.push(b'e');
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bencode;
+    use crate::metainfo::read::from_bytes;
+
+    #[test]
+    fn test_round_trip() {
+        let source = bencode! {
+            "announce" => "a",
+            "announce-list" => [["a"], ["b", "c"]],
+            "comment" => "hi",
+            "info" => {
+                "file tree" => {
+                    "c.txt" => { "" => { "length" => 4, "pieces root" => &[b'b'; 32] } },
+                    "dir" => {
+                        "b.txt" => { "" => { "length" => 3, "pieces root" => &[b'a'; 32] } },
+                    },
+                },
+                "files" => [
+                    { "length" => 3, "path" => ["dir", "b.txt"] },
+                    { "attr" => "p", "length" => 16381, "path" => [".pad", "16381"] },
+                    { "length" => 4, "path" => ["c.txt"] },
+                ],
+                "meta version" => 2,
+                "name" => "root",
+                "piece length" => 16384,
+                "pieces" => &[b'a'; 40],
+                "private" => 1,
+            },
+        }
+        .to_bytes();
+        let meta = from_bytes(&source).unwrap();
+        assert_eq!(meta.to_bytes(), source);
+        assert_eq!(meta.info.to_value().to_bytes(), meta.raw_info);
+    }
+
+    #[test]
+    fn test_raw_info_is_kept() {
+        let source: &[u8] =
+            b"d8:announce1:a4:infod4:name1:a6:lengthi0e12:piece lengthi1e6:pieces0:e7:zzzzzzzi0ee";
+        let meta = from_bytes(source).unwrap();
+        assert_eq!(
+            meta.to_bytes(),
+            b"d8:announce1:a4:infod4:name1:a6:lengthi0e12:piece lengthi1e6:pieces0:ee"
+        );
+    }
+}
@@ -0,0 +1,91 @@
+#!/usr/bin/env python3
+"""Writes v2.torrent, a BEP 52 torrent made independently of torr.
+
+The files are generated, not stored: byte i of `a` is i % 251 and byte i
+of `dir/b` is i % 13. The piece length is 32 KiB, so `a` has a piece layer
+and `dir/b` fits in one piece.
+"""
+
+import hashlib
+import os
+
+BLOCK = 16 * 1024
+PIECE_LENGTH = 32 * 1024
+FILES = {
+    ("a",): bytes(i % 251 for i in range(5 * BLOCK + 100)),
+    ("dir", "b"): bytes(i % 13 for i in range(20000)),
+}
+
+
+def bencode(value):
+    if isinstance(value, int):
+        return b"i%de" % value
+    if isinstance(value, str):
+        value = value.encode()
+    if isinstance(value, bytes):
+        return b"%d:%s" % (len(value), value)
+    if isinstance(value, list):
+        return b"l" + b"".join(bencode(v) for v in value) + b"e"
+    items = sorted((k.encode() if isinstance(k, str) else k, v) for k, v in value.items())
+    return b"d" + b"".join(bencode(k) + bencode(v) for k, v in items) + b"e"
+
+
+def sha256(data):
+    return hashlib.sha256(data).digest()
+
+
+def merkle_root(hashes, width, pad):
+    layer = list(hashes) + [pad] * (width - len(hashes))
+    while len(layer) > 1:
+        layer = [sha256(layer[i] + layer[i + 1]) for i in range(0, len(layer), 2)]
+    return layer[0]
+
+
+def next_power_of_two(n):
+    width = 1
+    while width < n:
+        width *= 2
+    return width
+
+
+def file_hashes(data):
+    """The pieces root and, for files of more than one piece, the piece layer."""
+    leaves = [sha256(data[i:i + BLOCK]) for i in range(0, len(data), BLOCK)]
+    blocks_per_piece = PIECE_LENGTH // BLOCK
+    if len(data) <= PIECE_LENGTH:
+        return merkle_root(leaves, next_power_of_two(len(leaves)), bytes(32)), None
+    pieces = [
+        merkle_root(leaves[i:i + blocks_per_piece], blocks_per_piece, bytes(32))
+        for i in range(0, len(leaves), blocks_per_piece)
+    ]
+    pad = merkle_root([], blocks_per_piece, bytes(32))
+    return merkle_root(pieces, next_power_of_two(len(pieces)), pad), b"".join(pieces)
+
+
+def main():
+    tree = {}
+    layers = {}
+    for path, data in FILES.items():
+        root, layer = file_hashes(data)
+        node = tree
+        for component in path:
+            node = node.setdefault(component, {})
+        node[""] = {"length": len(data), "pieces root": root}
+        if layer is not None:
+            layers[root] = layer
+    torrent = {
+        "info": {
+            "file tree": tree,
+            "meta version": 2,
+            "name": "v2",
+            "piece length": PIECE_LENGTH,
+        },
+        "piece layers": layers,
+    }
+    out = os.path.join(os.path.dirname(os.path.abspath(__file__)), "v2.torrent")
+    with open(out, "wb") as f:
+        f.write(bencode(torrent))
+
+
+if __name__ == "__main__":
+    main()